@@ -2,7 +2,11 @@ use std::{collections::VecDeque, sync::Mutex};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
-use crate::{level_compressor, level_loader};
+use crate::{
+    game_events::GameEvent, game_objects::game_rules::GameRules, game_status::GameStatus,
+    level_compressor, level_loader, level_pack::LevelPack, replay::Replay, save_state::SaveState,
+    solver,
+};
 
 #[derive(Debug)]
 pub enum Command {
@@ -13,6 +17,20 @@ pub enum Command {
     JoystickInput(f32, f32), // input as a vector (x, y)
     ActionButtonPressed,
     ActionButtonReleased,
+    StartRecording,
+    StopRecording,
+    PlayReplay(level_loader::ParsedLevel, Replay),
+    SetGameRules(GameRules),
+    LoadLevelPack(LevelPack),
+    // Loads the unlocked level with the given ID from the loaded level pack
+    LoadPackLevel(String),
+    ImportSaveState(SaveState),
+    QuickSave,
+    QuickLoad,
+    // The level is played backwards while the rewind button is held
+    RewindButtonPressed,
+    RewindButtonReleased,
+    UndoBoxPush,
 }
 
 pub struct CommandQueue {
@@ -37,6 +55,14 @@ impl CommandQueue {
 
 lazy_static::lazy_static! {
     pub static ref COMMANDS: CommandQueue = CommandQueue::new();
+    // The compressed replay of the last recording, set when the StopRecording command is processed
+    pub static ref RECORDED_REPLAY: Mutex<Option<String>> = Mutex::new(None);
+    // The status of the game after the last frame, set by the game loop
+    pub static ref GAME_STATUS: Mutex<GameStatus> = Mutex::new(GameStatus::default());
+    // The events of the game since the page took them the last time
+    pub static ref GAME_EVENTS: Mutex<Vec<GameEvent>> = Mutex::new(Vec::new());
+    // The save state after its last change, set by the game loop
+    pub static ref SAVE_STATE: Mutex<SaveState> = Mutex::new(SaveState::default());
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
@@ -56,15 +82,20 @@ pub fn load_level(level: &str) -> Result<(), String> {
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub fn check_level(level: &str) -> String {
     match level_loader::parse_level(level) {
-        Ok(_) => {
-            return String::from("{ \"result\": \"ok\" }");
-        }
-        Err(e) => {
-            return format!(
-                "{{ \"result\": \"error\", \"contents\": {} }}",
-                e.to_string()
-            );
-        }
+        Ok(parsed) => format!(
+            "{{ \"result\": \"ok\", \"solvability\": {} }}",
+            serde_json::to_string(&solver::check_solvability(&parsed)).unwrap()
+        ),
+        Err(e) => format!("{{ \"result\": \"error\", \"contents\": {} }}", e),
+    }
+}
+
+// Returns the metadata of the level as JSON, e.g. to show a title card
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn level_metadata(level: &str) -> Result<String, String> {
+    match level_loader::parse_level(level) {
+        Ok(parsed) => Ok(serde_json::to_string(parsed.metadata()).unwrap()),
+        Err(e) => Err(e.to_string()),
     }
 }
 
@@ -93,6 +124,121 @@ pub fn action_button_released() {
     COMMANDS.push(Command::ActionButtonReleased);
 }
 
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn start_recording() {
+    COMMANDS.push(Command::StartRecording);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn stop_recording() {
+    COMMANDS.push(Command::StopRecording);
+}
+
+// Returns the compressed replay once the recording has been stopped
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn take_recorded_replay() -> Option<String> {
+    RECORDED_REPLAY.lock().unwrap().take()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn play_replay(level: &str, replay: &str) -> Result<(), String> {
+    let replay = Replay::from_compressed_string(replay)?;
+    match level_loader::parse_level(level) {
+        Ok(parsed) => {
+            COMMANDS.push(Command::PlayReplay(parsed, replay));
+            Ok(())
+        }
+        Err(e) => {
+            log::info!("Error: {:?}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+// Returns the status of the game after the last frame as JSON, e.g. to draw a charge meter and a timer
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn get_game_status() -> String {
+    serde_json::to_string(&*GAME_STATUS.lock().unwrap()).unwrap()
+}
+
+// Returns the events of the game since the last call as a JSON array, e.g. to show messages or play sounds
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn take_game_events() -> String {
+    let events = std::mem::take(&mut *GAME_EVENTS.lock().unwrap());
+    serde_json::to_string(&events).unwrap()
+}
+
+// Sets the rules of levels that do not change them in their header
+// The rules are given as JSON, missing fields keep their default, e.g. { "gravity": -4.0 }
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_game_rules(game_rules: &str) -> Result<(), String> {
+    let game_rules = serde_json::from_str::<GameRules>(game_rules).map_err(|e| e.to_string())?;
+    COMMANDS.push(Command::SetGameRules(game_rules));
+    Ok(())
+}
+
+// Loads a level pack as created by compress_level_pack and starts its first level
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn load_level_pack(level_pack: &str) -> Result<(), String> {
+    let level_pack = LevelPack::from_compressed_string(level_pack)?;
+    COMMANDS.push(Command::LoadLevelPack(level_pack));
+    Ok(())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn load_pack_level(id: &str) {
+    COMMANDS.push(Command::LoadPackLevel(id.to_string()));
+}
+
+// Validates a level pack given as JSON and compresses it to be shared in an URL
+// e.g. { "version": 1, "title": "Pack", "author": null, "levels": [{ "id": "intro", "level": "N+P N+G(GO)" }] }
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn compress_level_pack(level_pack: &str) -> Result<String, String> {
+    Ok(LevelPack::from_json(level_pack)?.to_compressed_string())
+}
+
+// Keeps the state of the level, e.g. before a tricky jump
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn quick_save() {
+    COMMANDS.push(Command::QuickSave);
+}
+
+// Returns to the state kept by the last quick_save in the current level
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn quick_load() {
+    COMMANDS.push(Command::QuickLoad);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn rewind_button_pressed() {
+    COMMANDS.push(Command::RewindButtonPressed);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn rewind_button_released() {
+    COMMANDS.push(Command::RewindButtonReleased);
+}
+
+// Puts the boxes back where they were before the last grab, can be repeated to undo earlier pushes
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn undo_box_push() {
+    COMMANDS.push(Command::UndoBoxPush);
+}
+
+// Returns the progress and settings as a string the page can keep in the localStorage
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn export_save_state() -> String {
+    SAVE_STATE.lock().unwrap().to_json_string()
+}
+
+// Replaces the progress and settings with a string returned by export_save_state
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn import_save_state(save_state: &str) -> Result<(), String> {
+    let save_state = SaveState::from_json_string(save_state)?;
+    COMMANDS.push(Command::ImportSaveState(save_state));
+    Ok(())
+}
+
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub fn compress_level_to_url(level: &str) -> String {
     level_compressor::compress_level(level)
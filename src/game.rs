@@ -6,7 +6,9 @@ use rand::seq::IteratorRandom;
 use rapier3d::geometry::ColliderHandle;
 
 use crate::{
+    game_events::{DeathCause, GameEvent, GameEvents},
     game_objects::{
+        block_id::BlockId,
         charge::{
             charge_recharge_system, move_charge_ghost_system, player_charge_depletion_system,
             ChargeGhost, ChargeSpawnArea,
@@ -15,12 +17,17 @@ use crate::{
             self, animate_checkpoint_particles_system, set_checkpoint_system,
             spawn_checkpoint_particle_system, Checkpoint,
         },
-        constants::TICKS_PER_SECOND,
+        constants::{
+            DEFAULT_SEED, MAX_BOX_UNDO_STEPS, MAX_PLAYER_CHARGE, REWIND_MEMORY_BUDGET,
+            TICKS_PER_SECOND,
+        },
         dust::animate_dust_particle_system,
         firework::{firework_emitter_system, firework_particle_system, FireworkEmitter},
+        game_rng::GameRng,
+        game_rules::GameRules,
         game_system_commands::{GameSystemCommand, GameSystemCommands},
         glitch_area::GlitchAreaVisibility,
-        goal::{check_goal_reached_system, Goal},
+        goal::{check_goal_reached_system, Goal, NextLevel, PackLevelLinks},
         input::Input,
         model_manager::ModelManager,
         movable::{
@@ -29,23 +36,42 @@ use crate::{
             GrabContractionAnimation, Movable,
         },
         physics_body::PhysicsBody,
+        platform::{move_platform_system, Platform},
         player::{move_player_system, spawn_dust_on_move_player_system, Player},
         position::Position,
         renderable::Renderable,
         sensor::Sensor,
-        time_keeper::TimeKeeper,
+        teleporter::{
+            animate_teleporter_system, teleport_cooldown_system, teleport_system, TeleportCooldown,
+            Teleporter,
+        },
+        time_keeper::{TimeKeeper, TimeSource},
+        trigger::{active_trigger_ids, animate_trigger_system, update_trigger_system, Trigger},
     },
+    game_status::GameStatus,
     level_loader::{Cell, ParsedLevel},
+    level_pack::{LevelPack, LevelPackProgress},
     model::ModelHandle,
-    object_types::{Block, BlockType, Id, LinearEnemyDirection},
+    object_types::{
+        Block, BlockType, DoorCondition, EnemyParams, Id, LinearEnemyDirection, PatrolMode,
+        PatrolPath, StartDirection,
+    },
+    pathfinding::{GridPosition, NavGrid},
     physics::PhysicsSystem,
+    replay::{Replay, ReplayInput, ReplayPlayer, ReplayRecorder},
+    rewind::RewindBuffer,
+    save_state::{content_key, SaveState},
     stereo_camera::StereoCamera,
+    world_snapshot::{
+        EnemySnapshot, EntitySnapshot, PlatformSnapshot, SensorSnapshot, SnapshotKey, WorldSnapshot,
+    },
 };
 
 #[derive(Component)]
 struct Door {
+    id: Id,
     open: bool,
-    trigger_id: Id,
+    condition: DoorCondition,
 }
 
 #[derive(Component)]
@@ -60,25 +86,160 @@ struct Box;
 #[derive(Component)]
 struct DamageArea {
     damage: f32,
+    knockback: f32,
+    // Seconds the player can not move after a hit
+    stun: f32,
+}
+
+impl DamageArea {
+    fn new(params: &EnemyParams) -> Self {
+        Self {
+            damage: params.damage,
+            knockback: params.knockback,
+            stun: params.stun,
+        }
+    }
 }
 
 #[derive(Component)]
 struct LinearEnemy {
     current_movement_direction: Vector3<f32>,
     stuck_counter: u32,
+    // Cells per second
+    speed: f32,
+    // Ticks to wait before turning around when stuck
+    turn_delay: u32,
+}
+
+#[derive(Component)]
+struct PatrolEnemy {
+    // The waypoints in world coordinates, the first one is the start
+    waypoints: Vec<Vector3<f32>>,
+    mode: PatrolMode,
+    // Cells per second
+    speed: f32,
+    // The index of the waypoint the enemy walks to
+    target: usize,
+    // Ping-pong enemies walk the waypoints backwards after reaching the last one
+    forward: bool,
+}
+
+impl PatrolEnemy {
+    fn new(start: Vector3<f32>, path: &PatrolPath) -> Self {
+        // Rows of the level go into the negative y direction of the world
+        let waypoints = path
+            .waypoints()
+            .into_iter()
+            .map(|(x, y)| start + Vector3::new(x as f32, -y as f32, 0.0))
+            .collect::<Vec<_>>();
+        Self {
+            target: if waypoints.len() > 1 { 1 } else { 0 },
+            waypoints,
+            mode: path.mode,
+            speed: path.speed as f32,
+            forward: true,
+        }
+    }
+
+    fn next_target(&mut self) {
+        let last = self.waypoints.len() - 1;
+        if last == 0 {
+            return;
+        }
+        match self.mode {
+            PatrolMode::Loop => self.target = (self.target + 1) % (last + 1),
+            PatrolMode::PingPong => {
+                if self.target == last {
+                    self.forward = false;
+                } else if self.target == 0 {
+                    self.forward = true;
+                }
+                self.target = if self.forward {
+                    self.target + 1
+                } else {
+                    self.target - 1
+                };
+            }
+        }
+    }
+}
+
+// Chasing enemies notice the player within this many cells
+const CHASE_RADIUS: f32 = 3.0;
+// ... or within this many cells if nothing blocks the sight
+const CHASE_SIGHT_RANGE: f32 = 8.0;
+// Cells per second
+const CHASE_SPEED: f32 = 3.0;
+// The path to the player is searched again after this many ticks as the player keeps moving
+const CHASE_REPATH_TICKS: u32 = TICKS_PER_SECOND / 4;
+
+#[derive(Component)]
+struct ChasingEnemy {
+    // The enemy returns here after it gave up the chase
+    home: GridPosition,
+    chasing: bool,
+    // The cells the enemy walks through to reach the player or its home
+    path: Vec<GridPosition>,
+    repath_ticks: u32,
 }
 
 // This component is used to make an entity invisible
 #[derive(Component)]
 pub struct Invisible;
 
+type EventCallback = std::boxed::Box<dyn FnMut(&GameEvent)>;
+
 pub struct GameWorld {
     world: World,
     schedule: Schedule,
 
     model_manager: ModelManager,
     level: Option<ParsedLevel>,
+    // The level as it was loaded, before the player was moved to a checkpoint
+    initial_level: Option<ParsedLevel>,
     camera_aspect: f32,
+
+    // In headless mode no models are loaded and time only advances via step_ticks()
+    headless: bool,
+    // The game RNG is reseeded with this seed on every level (re)load
+    seed: u64,
+    // The goal texts of all goals reached since the last call to take_reached_goals()
+    reached_goals: Vec<String>,
+    // The rules of levels that do not change them in their header
+    game_rules: GameRules,
+    // The checkpoint the player respawns at, the last one reached since the level was loaded
+    checkpoint: Option<Id>,
+    // The tick the current level was loaded at
+    level_start_tick: u64,
+    // The level pack the goals lead through, kept when a single level is loaded
+    level_pack: Option<LevelPack>,
+    level_pack_progress: LevelPackProgress,
+    // The ID of the level pack level that is played, None if the level is not from the pack
+    pack_level: Option<String>,
+    // The progress and settings that survive a reload of the game
+    save_state: SaveState,
+    save_state_changed: bool,
+    // Identifies the level in the save state, see save_state::content_key
+    level_key: Option<String>,
+    // False if the level was resumed at a saved checkpoint, the time then does not count as best time
+    level_started_fresh: bool,
+    // The snapshot quick_load() returns to, dropped when another level is loaded
+    quick_save: Option<WorldSnapshot>,
+    // A snapshot of every tick of the current level, played backwards while rewinding
+    rewind_buffer: RewindBuffer,
+    rewinding: bool,
+    // The movable blocks at the start of every grab since the last reset, newest last
+    box_undo_stack: Vec<WorldSnapshot>,
+    // Taken after every frame for the host page
+    status: GameStatus,
+    // Receives every event, without a callback the events are kept until take_events() is called
+    event_callback: Option<EventCallback>,
+    events: Vec<GameEvent>,
+
+    // Records the player input while active
+    replay_recorder: Option<ReplayRecorder>,
+    // Replaces the player input while active
+    replay_player: Option<ReplayPlayer>,
 }
 
 // Move the camera to always look at the player
@@ -143,46 +304,52 @@ fn physics_system(
     }
 }
 
-fn fixed_update_system(mut time_keeper: ResMut<TimeKeeper>) {
-    time_keeper.tick();
-}
-
-fn check_player_dead_system(mut query: Query<(&Position, &mut Player), With<Player>>) {
+fn check_player_dead_system(
+    mut game_events: ResMut<GameEvents>,
+    mut query: Query<(&Position, &mut Player), With<Player>>,
+) {
     for (position, mut player) in &mut query {
-        if position.position.z < -1.0 {
-            player.dead = true;
-        }
-        if player.charge < 0.0 {
-            player.dead = true;
+        let cause = if position.position.z < -1.0 {
+            DeathCause::Fell
+        } else if player.charge < 0.0 {
+            DeathCause::ChargeDepleted
+        } else {
+            continue;
+        };
+        if !player.dead {
+            game_events.send(GameEvent::PlayerDied { cause });
         }
+        player.dead = true;
     }
 }
 
 fn door_system(
     mut commands: Commands,
+    mut game_events: ResMut<GameEvents>,
     mut query: Query<(&mut Door, Entity, &PhysicsBody)>,
-    trigger_query: Query<&Sensor>,
+    trigger_query: Query<(&Sensor, &Trigger)>,
     mut physics_system: ResMut<PhysicsSystem>,
 ) {
-    let triggered_trigger_ids = trigger_query
-        .iter()
-        .filter(|trigger| trigger.triggered)
-        .filter_map(|trigger| trigger.id.clone())
-        .collect::<HashSet<_>>();
+    let triggered_trigger_ids = active_trigger_ids(&trigger_query);
 
     for (mut door, entity, body) in &mut query {
-        let open = triggered_trigger_ids.contains(&door.trigger_id);
+        let open = door
+            .condition
+            .is_satisfied(&|id| triggered_trigger_ids.contains(id));
         if open != door.open {
             door.open = open;
 
             if door.open {
                 commands.entity(entity).insert(Invisible);
                 physics_system.set_rigid_body_state(body.body, false);
-                log::info!("Open door {:?}", door.trigger_id);
+                log::info!("Open door {}", door.condition);
+                game_events.send(GameEvent::DoorOpened {
+                    id: door.id.id().to_string(),
+                });
             } else {
                 commands.entity(entity).remove::<Invisible>();
                 physics_system.set_rigid_body_state(body.body, true);
-                log::info!("Close door {:?}", door.trigger_id);
+                log::info!("Close door {}", door.condition);
             }
         }
     }
@@ -214,10 +381,11 @@ fn damage_area_system(
                 // push the player away from the damage area
                 let player_position = player_position.position;
                 let damage_area_position = sensor_position.position;
-                let direction = (player_position - damage_area_position).normalize() * 5.0;
+                let direction =
+                    (player_position - damage_area_position).normalize() * damage_area.knockback;
                 physics_system.move_body(player_physics_body.body, direction, false);
                 // otherwise the player might get stuck in the damage area
-                input.player_paralized_cooldown = 0.2;
+                input.player_paralized_cooldown = damage_area.stun;
             }
         }
     }
@@ -243,8 +411,7 @@ fn move_linear_enemy_system(
             enemy.stuck_counter += 1;
 
             // Wait a bit before turning around
-            let stuck_counter_max = 20;
-            if enemy.stuck_counter > stuck_counter_max {
+            if enemy.stuck_counter > enemy.turn_delay {
                 enemy.current_movement_direction = -enemy.current_movement_direction;
                 enemy.stuck_counter = 0;
             }
@@ -252,25 +419,234 @@ fn move_linear_enemy_system(
             enemy.stuck_counter = 0;
         }
 
-        let enemy_max_speed = 4.0;
-        let direction = enemy.current_movement_direction * enemy_max_speed;
+        let direction = enemy.current_movement_direction * enemy.speed;
         physics_system.move_body(body.body, direction, true);
     }
 }
 
+// Patrol enemies walk from waypoint to waypoint, a waypoint counts as reached once the enemy
+// is closer to it than the distance it walks in one tick
+fn move_patrol_enemy_system(
+    time_keeper: Res<TimeKeeper>,
+    mut query: Query<(&mut PatrolEnemy, &Position, &PhysicsBody)>,
+    mut physics_system: ResMut<PhysicsSystem>,
+) {
+    // Only move enemies if we are in a physics tick
+    // Otherwise the physics system will be frame rate dependent
+    if !time_keeper.is_in_fixed_tick() {
+        return;
+    }
+
+    for (mut enemy, position, body) in &mut query {
+        let step = enemy.speed / TICKS_PER_SECOND as f32;
+        let mut to_target = enemy.waypoints[enemy.target] - position.position;
+        to_target.z = 0.0;
+        if to_target.magnitude() <= step.max(0.05) {
+            enemy.next_target();
+            to_target = enemy.waypoints[enemy.target] - position.position;
+            to_target.z = 0.0;
+        }
+
+        let velocity = if to_target.magnitude() > 0.001 {
+            to_target.normalize() * enemy.speed
+        } else {
+            Vector3::new(0.0, 0.0, 0.0)
+        };
+        physics_system.move_body(body.body, velocity, true);
+    }
+}
+
+// The cell and level an entity of height 1 stands in
+fn grid_position(position: &Position) -> GridPosition {
+    let (x, y) = position.get_cell();
+    (x, y, (position.position.z - 0.5).round() as i32)
+}
+
+// Chasing enemies start to chase the player when it is close or in sight and in the glitch area.
+// They give up when the player leaves the glitch area or reaches a checkpoint and walk back home.
+fn update_chasing_enemy_system(
+    time_keeper: Res<TimeKeeper>,
+    nav_grid: Res<NavGrid>,
+    glitch_area_visibility: Res<GlitchAreaVisibility>,
+    mut query: Query<(&mut ChasingEnemy, &Position)>,
+    player_query: Query<(Entity, &Position), With<Player>>,
+    door_query: Query<(&Door, &Position)>,
+    checkpoint_query: Query<&Sensor, With<Checkpoint>>,
+) {
+    if !time_keeper.is_in_fixed_tick() {
+        return;
+    }
+    let Some((player, player_position)) = player_query.iter().next() else {
+        return;
+    };
+
+    let player_cell = grid_position(player_position);
+    let player_on_checkpoint = checkpoint_query
+        .iter()
+        .any(|sensor| sensor.triggered_by.contains(&player));
+    let player_can_be_chased = !player_on_checkpoint
+        && glitch_area_visibility
+            .glitch_cells
+            .contains(&(player_cell.0, player_cell.1));
+    let open_doors = door_query
+        .iter()
+        .filter(|(door, _position)| door.open)
+        .map(|(_door, position)| grid_position(position))
+        .collect::<HashSet<_>>();
+    let is_door_open = |position| open_doors.contains(&position);
+
+    for (mut enemy, position) in &mut query {
+        let enemy_cell = grid_position(position);
+        if enemy.chasing && !player_can_be_chased {
+            enemy.chasing = false;
+            enemy.repath_ticks = 0;
+        } else if !enemy.chasing && player_can_be_chased && enemy_cell.2 == player_cell.2 {
+            let distance = (((player_cell.0 - enemy_cell.0).pow(2)
+                + (player_cell.1 - enemy_cell.1).pow(2)) as f32)
+                .sqrt();
+            if distance <= CHASE_RADIUS
+                || (distance <= CHASE_SIGHT_RANGE
+                    && nav_grid.has_line_of_sight(enemy_cell, player_cell, &is_door_open))
+            {
+                enemy.chasing = true;
+                enemy.repath_ticks = 0;
+            }
+        }
+
+        enemy.repath_ticks = enemy.repath_ticks.saturating_sub(1);
+        if enemy.repath_ticks == 0 {
+            let target = if enemy.chasing {
+                player_cell
+            } else {
+                enemy.home
+            };
+            enemy.path = nav_grid
+                .find_path(enemy_cell, target, &is_door_open)
+                .unwrap_or_default();
+            enemy.repath_ticks = CHASE_REPATH_TICKS;
+        }
+    }
+}
+
+// Chasing enemies walk from cell center to cell center along their path
+fn move_chasing_enemy_system(
+    time_keeper: Res<TimeKeeper>,
+    mut query: Query<(&mut ChasingEnemy, &Position, &PhysicsBody)>,
+    mut physics_system: ResMut<PhysicsSystem>,
+) {
+    if !time_keeper.is_in_fixed_tick() {
+        return;
+    }
+
+    for (mut enemy, position, body) in &mut query {
+        let mut velocity = Vector3::new(0.0, 0.0, 0.0);
+        while let Some(&(x, y, _level)) = enemy.path.first() {
+            let cell_center = Vector3::new(x as f32 + 0.5, -y as f32 - 0.5, position.position.z);
+            let to_cell_center = cell_center - position.position;
+            if to_cell_center.magnitude() > 0.1 {
+                velocity = to_cell_center.normalize() * CHASE_SPEED;
+                break;
+            }
+            enemy.path.remove(0);
+        }
+        physics_system.move_body(body.body, velocity, true);
+    }
+}
+
 impl GameWorld {
     pub fn new(handle_store: HashMap<BlockType, Vec<ModelHandle>>) -> Self {
+        Self::with_model_manager(ModelManager::new(handle_store), false)
+    }
+
+    /// Creates a game world that runs without a GPU, window or loaded models.
+    /// Time does not advance on its own, the simulation is driven by step_ticks().
+    pub fn new_headless() -> Self {
+        Self::with_model_manager(ModelManager::headless(), true)
+    }
+
+    fn with_model_manager(model_manager: ModelManager, headless: bool) -> Self {
         let mut game_world = Self {
             world: World::default(),
-            schedule: Schedule::default(),
-            model_manager: ModelManager::new(handle_store),
+            schedule: Self::build_schedule(),
+            model_manager,
             level: None,
+            initial_level: None,
             camera_aspect: 1.0,
+            headless,
+            seed: DEFAULT_SEED,
+            reached_goals: Vec::new(),
+            game_rules: GameRules::default(),
+            checkpoint: None,
+            level_start_tick: 0,
+            level_pack: None,
+            level_pack_progress: LevelPackProgress::default(),
+            pack_level: None,
+            save_state: SaveState::default(),
+            save_state_changed: false,
+            level_key: None,
+            level_started_fresh: true,
+            quick_save: None,
+            rewind_buffer: RewindBuffer::new(REWIND_MEMORY_BUDGET),
+            rewinding: false,
+            box_undo_stack: Vec::new(),
+            status: GameStatus::default(),
+            event_callback: None,
+            events: Vec::new(),
+            replay_recorder: None,
+            replay_player: None,
         };
         game_world.init();
         game_world
     }
 
+    // The schedule is only built once as init() is called again on every level reset
+    fn build_schedule() -> Schedule {
+        let mut schedule = Schedule::default();
+        // Systems without an explicit order always run in the same order
+        // so that a simulation can be reproduced
+        schedule.set_executor_kind(bevy_ecs::schedule::ExecutorKind::SingleThreaded);
+        // The physics system needs to run after the player system so that the player can move
+        schedule.add_systems(
+            (
+                move_player_system,
+                move_movable_object_with_player_system,
+                damage_area_system,
+                physics_system,
+                update_trigger_system,
+                move_platform_system,
+                (teleport_cooldown_system, teleport_system).chain(),
+                charge_recharge_system,
+                player_charge_depletion_system,
+                (
+                    move_linear_enemy_system,
+                    move_patrol_enemy_system,
+                    update_chasing_enemy_system,
+                    move_chasing_enemy_system,
+                )
+                    .chain(),
+                move_charge_ghost_system,
+                animate_moving_objects_system,
+                spawn_checkpoint_particle_system,
+                animate_checkpoint_particles_system,
+                animate_dust_particle_system,
+                spawn_dust_on_move_player_system,
+                spawn_dust_on_moving_objects_system,
+                firework_particle_system,
+                firework_emitter_system,
+            )
+                .chain(),
+        );
+        schedule.add_systems(move_camera_system);
+        schedule.add_systems(check_player_dead_system);
+        schedule.add_systems(door_system);
+        schedule.add_systems(check_goal_reached_system);
+        schedule.add_systems(set_checkpoint_system);
+        schedule.add_systems(animate_grab_contraction_system);
+        schedule.add_systems(animate_trigger_system);
+        schedule.add_systems(animate_teleporter_system);
+        schedule
+    }
+
     fn init(&mut self) {
         self.world.insert_resource(PhysicsSystem::new());
         self.world.insert_resource(Input {
@@ -287,48 +663,161 @@ impl GameWorld {
             50.0,
             -3.0, // view cross-eyed
         ));
+        if let Some(eye_distance) = self.save_state.settings.eye_distance {
+            self.set_eye_distance(eye_distance);
+        }
         self.world.insert_resource(self.model_manager.clone());
-        self.world
-            .insert_resource(TimeKeeper::new(TICKS_PER_SECOND));
+        // The time keeper is kept when the level is reset
+        if !self.world.contains_resource::<TimeKeeper>() {
+            let time_keeper = if self.headless {
+                TimeKeeper::with_time_source(TICKS_PER_SECOND, TimeSource::manual())
+            } else {
+                TimeKeeper::new(TICKS_PER_SECOND)
+            };
+            self.world.insert_resource(time_keeper);
+        }
         self.world.insert_resource(GlitchAreaVisibility {
             visibility: 0.0,
             glitch_cells: HashSet::new(),
         });
         self.world.insert_resource(GameSystemCommands::new());
-        // The physics system needs to run after the player system so that the player can move
-        self.schedule.add_systems(
-            (
-                fixed_update_system,
-                move_player_system,
-                move_movable_object_with_player_system,
-                damage_area_system,
-                physics_system,
-                charge_recharge_system,
-                player_charge_depletion_system,
-                move_linear_enemy_system,
-                move_charge_ghost_system,
-                animate_moving_objects_system,
-                spawn_checkpoint_particle_system,
-                animate_checkpoint_particles_system,
-                animate_dust_particle_system,
-                spawn_dust_on_move_player_system,
-                spawn_dust_on_moving_objects_system,
-                firework_particle_system,
-                firework_emitter_system,
-            )
-                .chain(),
-        );
-        self.schedule.add_systems(move_camera_system);
-        self.schedule.add_systems(check_player_dead_system);
-        self.schedule.add_systems(door_system);
-        self.schedule.add_systems(check_goal_reached_system);
-        self.schedule.add_systems(set_checkpoint_system);
-        self.schedule.add_systems(animate_grab_contraction_system);
+        self.world.insert_resource(NextLevel::default());
+        self.world.insert_resource(GameRng::new(self.seed));
+        self.world.insert_resource(NavGrid::default());
+        self.world.insert_resource(GameEvents::default());
+        self.apply_game_rules(self.game_rules);
+    }
+
+    // Makes the rules take effect in the running level
+    fn apply_game_rules(&mut self, game_rules: GameRules) {
+        self.world
+            .resource_mut::<PhysicsSystem>()
+            .set_gravity(game_rules.gravity);
+        self.world.insert_resource(game_rules);
     }
 
+    /// Runs as many fixed ticks as the elapsed time requires.
+    /// If no fixed tick is due the schedule is still run once to update animations.
     pub fn update(&mut self) {
+        let fixed_ticks = self.world.resource_mut::<TimeKeeper>().begin_frame();
+        if fixed_ticks == 0 {
+            self.world
+                .resource_mut::<TimeKeeper>()
+                .begin_variable_frame();
+            self.run_schedule();
+        }
+        for _ in 0..fixed_ticks {
+            if self.rewinding {
+                // The time keeps running, rewinding only replaces the simulation
+                self.world.resource_mut::<TimeKeeper>().begin_fixed_tick();
+                self.rewind_tick();
+                continue;
+            }
+            self.apply_replay_input();
+            self.world.resource_mut::<TimeKeeper>().begin_fixed_tick();
+            self.run_schedule();
+            if let Some(snapshot) = self.capture_snapshot(false) {
+                self.rewind_buffer.push(snapshot);
+            }
+        }
+        // Let the camera, doors and animations follow the restored state
+        if self.rewinding && fixed_ticks > 0 {
+            self.world
+                .resource_mut::<TimeKeeper>()
+                .begin_variable_frame();
+            self.run_schedule();
+        }
+
+        // The input of this frame has been applied to all of its fixed ticks
+        if fixed_ticks > 0 {
+            self.world.resource_mut::<Input>().player_movement = None;
+        }
+
+        self.status = self.take_status_snapshot();
+    }
+
+    fn take_status_snapshot(&mut self) -> GameStatus {
+        let Some(level) = &self.level else {
+            return GameStatus::default();
+        };
+        let par_time = level.metadata().par_time;
+        let player = self
+            .world
+            .query::<(&Player, &Position)>()
+            .iter(&self.world)
+            .next()
+            .map(|(player, position)| (player.charge, position.get_cell()));
+        let glitch_cells = &self.world.resource::<GlitchAreaVisibility>().glitch_cells;
+        let (charge, in_glitch_area) = player
+            .map(|(charge, cell)| (charge, glitch_cells.contains(&cell)))
+            .unwrap_or((0.0, false));
+
+        GameStatus {
+            level_loaded: true,
+            charge,
+            max_charge: MAX_PLAYER_CHARGE,
+            in_glitch_area,
+            checkpoint: self.checkpoint.as_ref().map(|id| id.id().to_string()),
+            elapsed_time: self.elapsed_time(),
+            par_time,
+            pack_level: self.pack_level.clone(),
+            level_pack_progress: self.level_pack_progress().cloned(),
+            rewinding: self.rewinding,
+            box_undo_steps: self.box_undo_stack.len(),
+        }
+    }
+
+    // Seconds since the level was loaded
+    fn elapsed_time(&self) -> f32 {
+        (self.tick_count() - self.level_start_tick) as f32 / TICKS_PER_SECOND as f32
+    }
+
+    /// Returns the snapshot of the game taken after the last frame
+    pub fn game_status(&self) -> &GameStatus {
+        &self.status
+    }
+
+    // Feeds the input of a running replay into the upcoming fixed tick or records the input of it
+    fn apply_replay_input(&mut self) {
+        let tick_count = self.tick_count();
+
+        if let Some(mut replay_player) = self.replay_player.take() {
+            if replay_player.is_finished(tick_count) {
+                log::info!("Replay finished");
+                return;
+            }
+            for input in replay_player.inputs_for_tick(tick_count) {
+                match input {
+                    ReplayInput::Move { .. } => {}
+                    ReplayInput::GrabPressed => self.grab(),
+                    ReplayInput::GrabReleased => self.release_grab(),
+                }
+            }
+            let (x, y) = replay_player.movement();
+            self.world.resource_mut::<Input>().player_movement =
+                Some(cgmath::Vector3::new(x, y, 0.0));
+            self.replay_player = Some(replay_player);
+        }
+
+        if let Some(replay_recorder) = self.replay_recorder.as_mut() {
+            let movement = self
+                .world
+                .resource::<Input>()
+                .player_movement
+                .unwrap_or(cgmath::Vector3::new(0.0, 0.0, 0.0));
+            replay_recorder.record_movement(tick_count, (movement.x, movement.y));
+        }
+    }
+
+    fn run_schedule(&mut self) {
         self.schedule.run(&mut self.world);
 
+        // The events have to be taken before a reset clears the world
+        let events = self.world.resource_mut::<GameEvents>().drain();
+        for event in events {
+            self.send_event(event);
+        }
+
         // if player is dead, reset the level
         let dead_player = self
             .world
@@ -341,13 +830,16 @@ impl GameWorld {
             self.reset_level();
         }
 
-        while let Some(command) = self
-            .world
-            .get_resource_mut::<GameSystemCommands>()
-            .unwrap()
-            .commands
-            .pop()
-        {
+        // Take all commands at once as loading a level replaces the command resource
+        let commands = std::mem::take(
+            &mut self
+                .world
+                .get_resource_mut::<GameSystemCommands>()
+                .unwrap()
+                .commands,
+        );
+        // In the order the systems sent them, so a goal is reached before the next level is loaded
+        for command in commands {
             match command {
                 GameSystemCommand::LoadLevel(level) => {
                     self.load_level(level);
@@ -355,20 +847,149 @@ impl GameWorld {
                 GameSystemCommand::SetCheckpoint(id) => {
                     log::info!("Set checkpoint {:?}", id);
                     self.level.as_mut().map(|level| {
-                        level.set_checkpoint(id);
+                        level.set_checkpoint(id.clone());
+                    });
+                    // The checkpoint is set again every tick the player stands on it
+                    if self.checkpoint.as_ref() != Some(&id) {
+                        self.send_event(GameEvent::CheckpointSet {
+                            id: id.id().to_string(),
+                        });
+                        if let Some(level_key) = &self.level_key {
+                            self.save_state
+                                .set_checkpoint(level_key, id.id().to_string());
+                            self.save_state_changed = true;
+                        }
+                    }
+                    self.checkpoint = Some(id);
+                }
+                GameSystemCommand::GoalReached(goal_level_text) => {
+                    log::info!("Goal reached {:?}", goal_level_text);
+                    self.send_event(GameEvent::GoalReached {
+                        text: goal_level_text.clone(),
                     });
+                    self.reached_goals.push(goal_level_text);
+                    if let Some(level_key) = &self.level_key {
+                        let time = self.level_started_fresh.then(|| self.elapsed_time());
+                        self.save_state.complete_level(level_key, time);
+                        self.save_state_changed = true;
+                    }
+                }
+                GameSystemCommand::CompletePackLevel(id) => {
+                    log::info!("Completed level pack level {:?}", id);
+                    self.level_pack_progress.complete(&id);
+                    self.store_level_pack_progress();
+                }
+                GameSystemCommand::LoadPackLevel(id) => {
+                    self.level_pack_progress.unlock(&id);
+                    self.store_level_pack_progress();
+                    if let Err(e) = self.load_pack_level(&id) {
+                        log::warn!("Failed to load the level pack level: {}", e);
+                    }
                 }
             }
         }
     }
 
+    /// Advances the manual clock by exactly one fixed tick and updates the world, n times.
+    /// Only has an effect on worlds created with new_headless().
+    /// Note that player movement is consumed every tick, so move_player() has to be called before every tick.
+    pub fn step_ticks(&mut self, n: u32) {
+        if !self.headless {
+            log::warn!("step_ticks() is only supported in headless mode");
+            return;
+        }
+
+        for _ in 0..n {
+            let mut time_keeper = self.world.resource_mut::<TimeKeeper>();
+            let ms_per_tick = time_keeper.ms_per_tick();
+            time_keeper.advance_manual_clock(ms_per_tick);
+            self.update();
+        }
+    }
+
     pub fn clear(&mut self) {
+        let time_keeper = self.world.remove_resource::<TimeKeeper>();
         self.world.clear_all();
+        if let Some(time_keeper) = time_keeper {
+            self.world.insert_resource(time_keeper);
+        }
         self.init();
     }
 
+    /// Sets the rules of levels that do not change them in their header.
+    /// The rules take effect immediately, values from the header of the current level still apply.
+    pub fn set_game_rules(&mut self, game_rules: GameRules) {
+        self.game_rules = game_rules;
+        let game_rules = match &self.level {
+            Some(level) => game_rules.with_metadata(level.metadata()),
+            None => game_rules,
+        };
+        self.apply_game_rules(game_rules);
+    }
+
+    /// Returns the rules of the running level
+    pub fn game_rules(&self) -> GameRules {
+        *self.world.resource::<GameRules>()
+    }
+
+    /// Sets the seed of the game RNG, it takes effect on the next level (re)load.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Returns the number of fixed ticks simulated since the game world was created
+    pub fn tick_count(&self) -> u64 {
+        self.world.resource::<TimeKeeper>().tick_count()
+    }
+
+    /// Replaces the clock the simulation is driven by (e.g. to run in slow motion)
+    pub fn set_time_source(&mut self, time_source: TimeSource) {
+        self.world
+            .resource_mut::<TimeKeeper>()
+            .set_time_source(time_source);
+    }
+
+    /// Restarts the current level from its start, not from a checkpoint, and records the player input from then on
+    pub fn start_recording(&mut self) {
+        self.replay_player = None;
+        self.rewinding = false;
+        // A replay is played back from the start of the level
+        match self.initial_level.clone() {
+            Some(level) => self.start_level(level, false),
+            None => self.reset_level(),
+        }
+        self.replay_recorder = Some(ReplayRecorder::new(self.seed, self.tick_count()));
+    }
+
+    /// Stops the recording and returns the recorded replay or None if nothing was recorded
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        let tick_count = self.tick_count();
+        self.replay_recorder
+            .take()
+            .map(|replay_recorder| replay_recorder.finish(tick_count))
+    }
+
+    /// Loads the level and plays back the replay on it, live input is ignored until the replay has finished.
+    pub fn play_replay(&mut self, level: ParsedLevel, replay: Replay) {
+        self.replay_recorder = None;
+        self.set_seed(replay.seed);
+        // The replay was recorded from the start of the level, not from a saved checkpoint
+        self.pack_level = None;
+        self.start_level(level, false);
+        self.replay_player = Some(ReplayPlayer::new(replay, self.tick_count()));
+    }
+
+    /// Returns true while a replay is played back
+    pub fn is_playing_replay(&self) -> bool {
+        self.replay_player.is_some()
+    }
+
     pub fn reset_level(&mut self) {
         self.clear();
+        // The reset already brought the boxes back
+        self.box_undo_stack.clear();
+        // Rewinding does not bring the player back across a death
+        self.rewind_buffer.clear();
         if let Some(level) = self.level.take() {
             let mut glitch_area = HashSet::new();
             for ((x, y), cell) in level.iter_cells() {
@@ -381,51 +1002,493 @@ impl GameWorld {
                 .get_resource_mut::<GlitchAreaVisibility>()
                 .unwrap()
                 .glitch_cells = glitch_area;
+            self.world.insert_resource(NavGrid::from_level(&level));
+
+            let metadata = level.metadata();
+            if let Some(eye_distance) = metadata.eye_distance {
+                self.set_eye_distance(eye_distance);
+            }
+            let pack_level = self.pack_level.as_ref().map(|id| PackLevelLinks {
+                id: id.clone(),
+                next_id: self
+                    .level_pack
+                    .as_ref()
+                    .and_then(|pack| pack.next_level_id(id))
+                    .map(String::from),
+            });
+            let mut next_level = self.world.resource_mut::<NextLevel>();
+            next_level.compressed_level = metadata.next.clone();
+            next_level.pack_level = pack_level;
+            self.apply_game_rules(self.game_rules.with_metadata(metadata));
             self.level = Some(level);
         }
     }
 
+    /// Loads the level, the player starts at the checkpoint reached in an earlier session if there is one
     pub fn load_level(&mut self, level: ParsedLevel) {
-        self.level = Some(level);
-        self.reset_level();
+        self.pack_level = None;
+        self.start_level(level, true);
     }
 
-    fn add_cell(&mut self, x: i32, y: i32, cell: &Cell) {
-        let mut z = 0.0;
-        for (block, id) in cell.block_stack_iter() {
-            if block != &Block::Empty {
-                let position = Position {
-                    position: cgmath::Vector3::new(
-                        x as f32 + 0.5,
-                        -y as f32 - 0.5,
-                        z as f32 + block.block_height() / 2.0,
-                    ),
-                    rotation: cgmath::Quaternion::from_axis_angle(
-                        cgmath::Vector3::unit_z(),
-                        cgmath::Deg(0.0),
-                    ),
-                    color: cgmath::Vector4::new(1.0, 1.0, 1.0, 1.0),
-                    scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
-                    grabbed_scale_factor: 1.0,
-                    grabbed_rotation: cgmath::Quaternion::one(),
-                };
+    /// Loads the first level of the pack with the progress kept in the save state
+    pub fn load_level_pack(&mut self, level_pack: LevelPack) -> Result<(), String> {
+        self.level_pack_progress = self
+            .save_state
+            .level_packs
+            .get(&level_pack.key())
+            .cloned()
+            .unwrap_or_else(|| LevelPackProgress::new(&level_pack));
+        // An imported save state may lack the first level
+        self.level_pack_progress.unlock(level_pack.first_level_id());
+        let first_level_id = level_pack.first_level_id().to_string();
+        self.level_pack = Some(level_pack);
+        self.load_pack_level(&first_level_id)
+    }
 
-                // to prevent the boxes from getting stuck in each other
-                let offset = 0.94;
-                let xy_size = match block.get_block_type() {
-                    BlockType::Player => 0.5 * 0.99,
-                    _ => 0.5,
-                };
-                let (body_handle, collider_handle) =
-                    self.world.resource_mut::<PhysicsSystem>().add_object(
-                        position.position.x,
-                        position.position.y,
-                        position.position.z,
-                        offset * xy_size,
-                        offset * xy_size,
-                        offset * block.block_height() / 2.0,
-                        &block,
-                    );
+    /// Loads an unlocked level of the current level pack
+    pub fn load_pack_level(&mut self, id: &str) -> Result<(), String> {
+        let level_pack = self.level_pack.as_ref().ok_or("No level pack loaded")?;
+        let level = level_pack
+            .level(id)
+            .ok_or_else(|| format!("Unknown level #{}", id))?;
+        if !self.level_pack_progress.is_unlocked(id) {
+            return Err(format!("Level #{} is locked", id));
+        }
+        self.pack_level = Some(id.to_string());
+        self.start_level(level, true);
+        Ok(())
+    }
+
+    // Keeps the progress of the current level pack in the save state
+    fn store_level_pack_progress(&mut self) {
+        if let Some(level_pack) = &self.level_pack {
+            self.save_state
+                .level_packs
+                .insert(level_pack.key(), self.level_pack_progress.clone());
+            self.save_state_changed = true;
+        }
+    }
+
+    /// Replaces the save state, e.g. with the one the host page kept from an earlier session
+    pub fn set_save_state(&mut self, save_state: SaveState) {
+        self.save_state = save_state;
+        self.save_state_changed = true;
+        if let Some(eye_distance) = self.save_state.settings.eye_distance {
+            self.set_eye_distance(eye_distance);
+        }
+        if let Some(level_pack) = &self.level_pack {
+            if let Some(progress) = self.save_state.level_packs.get(&level_pack.key()) {
+                self.level_pack_progress = progress.clone();
+                self.level_pack_progress.unlock(level_pack.first_level_id());
+            }
+        }
+    }
+
+    pub fn save_state(&self) -> &SaveState {
+        &self.save_state
+    }
+
+    /// Returns true once after the save state changed, so the host only has to store it then
+    pub fn take_save_state_changed(&mut self) -> bool {
+        std::mem::take(&mut self.save_state_changed)
+    }
+
+    /// Sets the eye distance the player chose, it is kept in the save state and applied to every level
+    /// that does not set its own
+    pub fn set_eye_distance_setting(&mut self, eye_distance: f32) {
+        self.save_state.settings.eye_distance = Some(eye_distance);
+        self.save_state_changed = true;
+        self.set_eye_distance(eye_distance);
+    }
+
+    /// Returns the unlocked and completed levels of the current level pack, None if no pack is loaded
+    pub fn level_pack_progress(&self) -> Option<&LevelPackProgress> {
+        self.level_pack
+            .as_ref()
+            .map(|_level_pack| &self.level_pack_progress)
+    }
+
+    /// Returns the level that is played
+    pub fn level(&self) -> Option<&ParsedLevel> {
+        self.level.as_ref()
+    }
+
+    /// Captures the state of the level, None if no level is loaded
+    pub fn take_snapshot(&mut self) -> Option<WorldSnapshot> {
+        self.capture_snapshot(true)
+    }
+
+    // Blocks with a fixed body and without a sensor (floors, walls, doors, ...) can be left out
+    // of snapshots that are taken every tick, doors follow the restored triggers on the next run
+    fn capture_snapshot(&mut self, include_fixed: bool) -> Option<WorldSnapshot> {
+        let level_key = self.level_key.clone()?;
+        let keys = self.snapshot_keys();
+        let mut query = self.world.query::<(
+            Entity,
+            &Position,
+            &PhysicsBody,
+            Option<&Player>,
+            Option<&Sensor>,
+            Option<&ChargeSpawnArea>,
+            Option<&Trigger>,
+        )>();
+        let physics_system = self.world.resource::<PhysicsSystem>();
+        let entities = query
+            .iter(&self.world)
+            .filter(|(_, _, physics_body, _, sensor, _, _)| {
+                include_fixed || sensor.is_some() || !physics_system.is_fixed(physics_body.body)
+            })
+            .filter_map(
+                |(entity, position, physics_body, player, sensor, charge_spawn_area, trigger)| {
+                    let entity_snapshot = EntitySnapshot {
+                        position: *position,
+                        body: physics_system.get_rigid_body_state(physics_body.body),
+                        charge: player.map(|player| player.charge),
+                        sensor: sensor.map(|sensor| SensorSnapshot {
+                            triggered: sensor.triggered,
+                            triggered_by: sensor
+                                .triggered_by
+                                .iter()
+                                .filter_map(|entity| keys.get(entity))
+                                .cloned()
+                                .collect(),
+                        }),
+                        charge_cooldown: charge_spawn_area
+                            .map(|charge_spawn_area| charge_spawn_area.cooldown_left),
+                        trigger: trigger.cloned(),
+                        platform: self.world.get::<Platform>(entity).map(|platform| {
+                            PlatformSnapshot {
+                                progress: platform.progress,
+                                moving_to_end: platform.moving_to_end,
+                            }
+                        }),
+                        enemy: self.enemy_snapshot(entity),
+                        teleport_cooldown: self
+                            .world
+                            .get::<TeleportCooldown>(entity)
+                            .map(|cooldown| cooldown.remaining_ticks),
+                        pulled_objects: player.map_or(Vec::new(), |player| {
+                            player
+                                .pulled_objects
+                                .iter()
+                                .filter_map(|entity| keys.get(entity))
+                                .cloned()
+                                .collect()
+                        }),
+                    };
+                    Some((keys.get(&entity)?.clone(), entity_snapshot))
+                },
+            )
+            .collect();
+
+        Some(WorldSnapshot {
+            level_key,
+            entities,
+        })
+    }
+
+    /// Brings the level back to the state of the snapshot, which has to be taken in the same level.
+    /// Entities are matched by their block, so the snapshot can also be restored after a reset.
+    pub fn restore_snapshot(&mut self, snapshot: &WorldSnapshot) -> Result<(), String> {
+        if self.level_key.as_ref() != Some(&snapshot.level_key) {
+            return Err("The snapshot was taken in another level".to_string());
+        }
+
+        let entities = self
+            .snapshot_keys()
+            .into_iter()
+            .map(|(entity, key)| (key, entity))
+            .collect::<HashMap<_, _>>();
+        for (key, entity_snapshot) in &snapshot.entities {
+            let Some(&entity) = entities.get(key) else {
+                continue;
+            };
+            let body = self.world.get::<PhysicsBody>(entity).unwrap().body;
+            self.world
+                .resource_mut::<PhysicsSystem>()
+                .restore_rigid_body_state(body, &entity_snapshot.body);
+            *self.world.get_mut::<Position>(entity).unwrap() = entity_snapshot.position;
+            if let (Some(charge), Some(mut player)) =
+                (entity_snapshot.charge, self.world.get_mut::<Player>(entity))
+            {
+                player.charge = charge;
+            }
+            if let (Some(sensor_snapshot), Some(mut sensor)) = (
+                &entity_snapshot.sensor,
+                self.world.get_mut::<Sensor>(entity),
+            ) {
+                sensor.triggered = sensor_snapshot.triggered;
+                sensor.triggered_by = sensor_snapshot
+                    .triggered_by
+                    .iter()
+                    .filter_map(|key| entities.get(key))
+                    .copied()
+                    .collect();
+            }
+            if let (Some(charge_cooldown), Some(mut charge_spawn_area)) = (
+                entity_snapshot.charge_cooldown,
+                self.world.get_mut::<ChargeSpawnArea>(entity),
+            ) {
+                charge_spawn_area.cooldown_left = charge_cooldown;
+            }
+            if let (Some(trigger_snapshot), Some(mut trigger)) = (
+                &entity_snapshot.trigger,
+                self.world.get_mut::<Trigger>(entity),
+            ) {
+                *trigger = trigger_snapshot.clone();
+            }
+            if let (Some(platform_snapshot), Some(mut platform)) = (
+                &entity_snapshot.platform,
+                self.world.get_mut::<Platform>(entity),
+            ) {
+                platform.progress = platform_snapshot.progress;
+                platform.moving_to_end = platform_snapshot.moving_to_end;
+                // Restoring the body dropped the target the platform moves to in the next step
+                let target = platform.position();
+                self.world
+                    .resource_mut::<PhysicsSystem>()
+                    .set_kinematic_target(body, target);
+            }
+            if let Some(enemy_snapshot) = &entity_snapshot.enemy {
+                self.restore_enemy(entity, enemy_snapshot);
+            }
+            match entity_snapshot.teleport_cooldown {
+                Some(remaining_ticks) => {
+                    self.world
+                        .entity_mut(entity)
+                        .insert(TeleportCooldown { remaining_ticks });
+                }
+                None => {
+                    self.world.entity_mut(entity).remove::<TeleportCooldown>();
+                }
+            }
+            if let Some(mut player) = self.world.get_mut::<Player>(entity) {
+                player.pulled_objects = entity_snapshot
+                    .pulled_objects
+                    .iter()
+                    .filter_map(|key| entities.get(key))
+                    .copied()
+                    .collect();
+            }
+        }
+        Ok(())
+    }
+
+    fn enemy_snapshot(&self, entity: Entity) -> Option<EnemySnapshot> {
+        if let Some(enemy) = self.world.get::<LinearEnemy>(entity) {
+            return Some(EnemySnapshot::Linear {
+                direction: enemy.current_movement_direction,
+                stuck_counter: enemy.stuck_counter,
+            });
+        }
+        if let Some(enemy) = self.world.get::<PatrolEnemy>(entity) {
+            return Some(EnemySnapshot::Patrol {
+                target: enemy.target,
+                forward: enemy.forward,
+            });
+        }
+        self.world
+            .get::<ChasingEnemy>(entity)
+            .map(|enemy| EnemySnapshot::Chasing {
+                chasing: enemy.chasing,
+                path: enemy.path.clone(),
+                repath_ticks: enemy.repath_ticks,
+            })
+    }
+
+    fn restore_enemy(&mut self, entity: Entity, enemy_snapshot: &EnemySnapshot) {
+        match enemy_snapshot {
+            EnemySnapshot::Linear {
+                direction,
+                stuck_counter,
+            } => {
+                if let Some(mut enemy) = self.world.get_mut::<LinearEnemy>(entity) {
+                    enemy.current_movement_direction = *direction;
+                    enemy.stuck_counter = *stuck_counter;
+                }
+            }
+            EnemySnapshot::Patrol { target, forward } => {
+                if let Some(mut enemy) = self.world.get_mut::<PatrolEnemy>(entity) {
+                    enemy.target = *target;
+                    enemy.forward = *forward;
+                }
+            }
+            EnemySnapshot::Chasing {
+                chasing,
+                path,
+                repath_ticks,
+            } => {
+                if let Some(mut enemy) = self.world.get_mut::<ChasingEnemy>(entity) {
+                    enemy.chasing = *chasing;
+                    enemy.path = path.clone();
+                    enemy.repath_ticks = *repath_ticks;
+                }
+            }
+        }
+    }
+
+    // The snapshot keys of all entities spawned for the blocks of the level
+    fn snapshot_keys(&mut self) -> HashMap<Entity, SnapshotKey> {
+        self.world
+            .query::<(Entity, &BlockId, Option<&Player>)>()
+            .iter(&self.world)
+            .map(|(entity, block_id, player)| {
+                let key = match player {
+                    Some(_) => SnapshotKey::Player,
+                    None => SnapshotKey::Block(block_id.0.clone()),
+                };
+                (entity, key)
+            })
+            .collect()
+    }
+
+    /// Keeps a snapshot of the level to return to with quick_load()
+    pub fn quick_save(&mut self) {
+        self.quick_save = self.take_snapshot();
+    }
+
+    /// Returns to the last quick save of the level, the quick save is kept to return to it again
+    pub fn quick_load(&mut self) {
+        if let Some(snapshot) = self.quick_save.clone() {
+            if let Err(e) = self.restore_snapshot(&snapshot) {
+                log::warn!("Failed to load the quick save: {}", e);
+            }
+        }
+    }
+
+    /// Plays the level backwards tick by tick until stop_rewind() is called, at most back to the last reset.
+    /// Has no effect while a replay is recorded or played back as the replay would no longer match.
+    pub fn start_rewind(&mut self) {
+        if self.replay_recorder.is_some() || self.replay_player.is_some() {
+            return;
+        }
+        self.rewinding = true;
+    }
+
+    /// Resumes the simulation from the state the level was rewound to
+    pub fn stop_rewind(&mut self) {
+        self.rewinding = false;
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    /// Puts the movable blocks back where they were when the last grab started.
+    /// Has no effect while a replay is recorded or played back as the replay would no longer match.
+    pub fn undo_box_push(&mut self) {
+        if self.replay_recorder.is_some() || self.replay_player.is_some() {
+            return;
+        }
+        let Some(snapshot) = self.box_undo_stack.pop() else {
+            return;
+        };
+        self.release_grab();
+        if let Err(e) = self.restore_snapshot(&snapshot) {
+            log::warn!("Failed to undo the box push: {}", e);
+        }
+    }
+
+    // Keeps the state of the boxes for undo_box_push()
+    fn record_box_undo_step(&mut self) {
+        // Enemies are movable as well but are not affected by an undo
+        let box_keys = self
+            .world
+            .query_filtered::<&BlockId, With<Box>>()
+            .iter(&self.world)
+            .map(|block_id| SnapshotKey::Block(block_id.0.clone()))
+            .collect::<HashSet<_>>();
+        let Some(mut snapshot) = self.capture_snapshot(false) else {
+            return;
+        };
+        snapshot.entities.retain(|key, _| box_keys.contains(key));
+        // Grabbing again without moving a box is no step to undo
+        if self.box_undo_stack.last() == Some(&snapshot) {
+            return;
+        }
+        if self.box_undo_stack.len() == MAX_BOX_UNDO_STEPS {
+            self.box_undo_stack.remove(0);
+        }
+        self.box_undo_stack.push(snapshot);
+    }
+
+    // Restores the newest snapshot of the rewind buffer, the level stays at the oldest one when it is used up
+    fn rewind_tick(&mut self) {
+        if let Some(snapshot) = self.rewind_buffer.pop() {
+            if let Err(e) = self.restore_snapshot(&snapshot) {
+                log::warn!("Failed to rewind: {}", e);
+            }
+        }
+    }
+
+    fn start_level(&mut self, mut level: ParsedLevel, resume_at_checkpoint: bool) {
+        self.initial_level = Some(level.clone());
+        let level_key = content_key(&level.to_level_string());
+        let checkpoint = self
+            .save_state
+            .checkpoint(&level_key)
+            .map(|id| Id::new(id.to_string()))
+            .filter(|id| resume_at_checkpoint && level.has_checkpoint(id));
+        if let Some(checkpoint) = &checkpoint {
+            level.set_checkpoint(checkpoint.clone());
+        }
+        self.level_started_fresh = checkpoint.is_none();
+        self.checkpoint = checkpoint;
+        self.level_key = Some(level_key);
+        self.level = Some(level);
+        self.quick_save = None;
+        self.rewind_buffer.clear();
+        self.rewinding = false;
+        self.level_start_tick = self.tick_count();
+        self.reset_level();
+        let title = self
+            .level
+            .as_ref()
+            .and_then(|level| level.metadata().title.clone());
+        self.send_event(GameEvent::LevelLoaded { title });
+    }
+
+    /// Returns the current level as level text, the player starts at the last reached checkpoint
+    pub fn level_string(&self) -> Option<String> {
+        self.level.as_ref().map(|level| level.to_level_string())
+    }
+
+    fn add_cell(&mut self, x: i32, y: i32, cell: &Cell) {
+        let mut z = 0.0;
+        for (block, id) in cell.blocks_with_ids((x, y)) {
+            if block != &Block::Empty {
+                let position = Position {
+                    position: cgmath::Vector3::new(
+                        x as f32 + 0.5,
+                        -y as f32 - 0.5,
+                        z as f32 + block.block_height() / 2.0,
+                    ),
+                    rotation: cgmath::Quaternion::from_axis_angle(
+                        cgmath::Vector3::unit_z(),
+                        cgmath::Deg(0.0),
+                    ),
+                    color: cgmath::Vector4::new(1.0, 1.0, 1.0, 1.0),
+                    scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+                    grabbed_scale_factor: 1.0,
+                    grabbed_rotation: cgmath::Quaternion::one(),
+                };
+
+                // to prevent the boxes from getting stuck in each other
+                let offset = 0.94;
+                let xy_size = match block.get_block_type() {
+                    BlockType::Player => 0.5 * 0.99,
+                    _ => 0.5,
+                };
+                let (body_handle, collider_handle) =
+                    self.world.resource_mut::<PhysicsSystem>().add_object(
+                        position.position.x,
+                        position.position.y,
+                        position.position.z,
+                        offset * xy_size,
+                        offset * xy_size,
+                        offset * block.block_height() / 2.0,
+                        &block,
+                    );
 
                 // Add sensor
                 let sensor_trigger = match block.get_block_type() {
@@ -434,6 +1497,12 @@ impl GameWorld {
                             .resource_mut::<PhysicsSystem>()
                             .add_sensor_collider(body_handle, 0.5, 0.5, 0.2, 0.0, 0.0, 0.05),
                     ),
+                    // Smaller than the tile so that travelers arrive inside the partner sensor
+                    BlockType::Teleporter => Some(
+                        self.world
+                            .resource_mut::<PhysicsSystem>()
+                            .add_sensor_collider(body_handle, 0.4, 0.4, 0.3, 0.0, 0.0, 0.3),
+                    ),
                     BlockType::Charge => Some(
                         self.world
                             .resource_mut::<PhysicsSystem>()
@@ -441,18 +1510,42 @@ impl GameWorld {
                     ),
                     BlockType::StaticEnemy
                     | BlockType::LinearEnemy
+                    | BlockType::PatrolEnemy
+                    | BlockType::ChasingEnemy
                     | BlockType::Goal
                     | BlockType::Checkpoint => Some(
                         self.world
                             .resource_mut::<PhysicsSystem>()
                             .add_sensor_collider(body_handle, 0.55, 0.55, 0.55, 0.0, 0.0, 0.0),
                     ),
+                    // Detects what stands on top of the platform
+                    BlockType::Platform => Some(
+                        self.world
+                            .resource_mut::<PhysicsSystem>()
+                            .add_sensor_collider(body_handle, 0.45, 0.45, 0.15, 0.0, 0.0, 0.55),
+                    ),
                     _ => None,
                 };
 
-                let mut entity = self
-                    .world
-                    .spawn((position, PhysicsBody { body: body_handle }));
+                // Checkpoints are only visible through their particles
+                let mesh_handle = if block.get_block_type() != BlockType::Checkpoint {
+                    let mut rng = self.world.resource_mut::<GameRng>();
+                    let handle = self
+                        .model_manager
+                        .get_handle(&block.get_block_type(), &mut *rng);
+                    if handle.is_none() {
+                        log::warn!("No mesh for block type {:?}", block.get_block_type());
+                    }
+                    handle
+                } else {
+                    None
+                };
+
+                let mut entity = self.world.spawn((
+                    position,
+                    PhysicsBody { body: body_handle },
+                    BlockId(id.clone()),
+                ));
 
                 match block {
                     Block::Player => {
@@ -462,23 +1555,25 @@ impl GameWorld {
                             charge: 0.0,
                         });
                     }
-                    Block::Goal(text) => {
+                    Block::Goal(text, level) => {
                         entity.insert((
                             Goal {
                                 goal_level_text: text.clone(),
+                                level: level.clone(),
                             },
                             Sensor {
                                 collider: sensor_trigger.unwrap(),
                                 triggered: false,
-                                id: id.clone(),
+                                id: Some(id.clone()),
                                 triggered_by: HashSet::new(),
                             },
                         ));
                     }
-                    Block::Door(trigger_id) => {
+                    Block::Door(condition) => {
                         entity.insert(Door {
+                            id: id.clone(),
                             open: false,
-                            trigger_id: trigger_id.clone(),
+                            condition: condition.clone(),
                         });
                     }
                     Block::Wall => {
@@ -490,13 +1585,16 @@ impl GameWorld {
                     Block::Box(_) => {
                         entity.insert((Box, Movable::default()));
                     }
-                    Block::Trigger => {
-                        entity.insert(Sensor {
-                            collider: sensor_trigger.unwrap(),
-                            triggered: false,
-                            id: id.clone(),
-                            triggered_by: HashSet::new(),
-                        });
+                    Block::Trigger(mode) => {
+                        entity.insert((
+                            Sensor {
+                                collider: sensor_trigger.unwrap(),
+                                triggered: false,
+                                id: Some(id.clone()),
+                                triggered_by: HashSet::new(),
+                            },
+                            Trigger::new(*mode),
+                        ));
                     }
                     Block::Charge => {
                         entity.insert((
@@ -510,7 +1608,7 @@ impl GameWorld {
                             Invisible,
                         ));
                     }
-                    Block::StaticEnemy => {
+                    Block::StaticEnemy(params) => {
                         entity.insert((
                             Sensor {
                                 collider: sensor_trigger.unwrap(),
@@ -518,10 +1616,14 @@ impl GameWorld {
                                 id: None,
                                 triggered_by: HashSet::new(),
                             },
-                            DamageArea { damage: 10.0 },
+                            DamageArea::new(params),
                         ));
                     }
-                    Block::LinearEnemy(direction) => {
+                    Block::LinearEnemy(direction, params) => {
+                        let axis = match direction {
+                            LinearEnemyDirection::XAxis => Vector3::unit_x(),
+                            LinearEnemyDirection::YAxis => Vector3::unit_y(),
+                        };
                         entity.insert((
                             Sensor {
                                 collider: sensor_trigger.unwrap(),
@@ -530,45 +1632,90 @@ impl GameWorld {
                                 triggered_by: HashSet::new(),
                             },
                             LinearEnemy {
-                                current_movement_direction: match direction {
-                                    LinearEnemyDirection::XAxis => Vector3::unit_x(),
-                                    LinearEnemyDirection::YAxis => Vector3::unit_y(),
+                                current_movement_direction: match params.start {
+                                    StartDirection::Forward => axis,
+                                    StartDirection::Backward => -axis,
                                 },
                                 stuck_counter: 0,
+                                speed: params.speed,
+                                turn_delay: params.turn_delay,
                             },
-                            DamageArea { damage: 10.0 },
+                            DamageArea::new(params),
                             Movable::default(),
                         ));
                     }
+                    Block::PatrolEnemy(path, params) => {
+                        entity.insert((
+                            Sensor {
+                                collider: sensor_trigger.unwrap(),
+                                triggered: false,
+                                id: None,
+                                triggered_by: HashSet::new(),
+                            },
+                            PatrolEnemy::new(position.position, path),
+                            DamageArea::new(params),
+                        ));
+                    }
+                    Block::ChasingEnemy(params) => {
+                        entity.insert((
+                            Sensor {
+                                collider: sensor_trigger.unwrap(),
+                                triggered: false,
+                                id: None,
+                                triggered_by: HashSet::new(),
+                            },
+                            ChasingEnemy {
+                                home: grid_position(&position),
+                                chasing: false,
+                                path: Vec::new(),
+                                repath_ticks: 0,
+                            },
+                            DamageArea::new(params),
+                        ));
+                    }
                     Block::Empty => {}
                     Block::Checkpoint => {
                         entity.insert((
                             Sensor {
                                 collider: sensor_trigger.unwrap(),
                                 triggered: false,
-                                id: id.clone(),
+                                id: Some(id.clone()),
                                 triggered_by: HashSet::new(),
                             },
-                            Checkpoint::new(id.clone().unwrap()),
+                            Checkpoint::new(id.clone()),
                         ));
                     }
-                    Block::FireworkEmitter => {
-                        entity.insert(FireworkEmitter::new());
+                    Block::Platform(path) => {
+                        entity.insert((
+                            Sensor {
+                                collider: sensor_trigger.unwrap(),
+                                triggered: false,
+                                id: None,
+                                triggered_by: HashSet::new(),
+                            },
+                            Platform::new(position.position, path),
+                        ));
+                    }
+                    Block::Teleporter(pair_id) => {
+                        entity.insert((
+                            Sensor {
+                                collider: sensor_trigger.unwrap(),
+                                triggered: false,
+                                id: None,
+                                triggered_by: HashSet::new(),
+                            },
+                            Teleporter::new(pair_id.clone()),
+                        ));
+                    }
+                    Block::FireworkEmitter(trigger) => {
+                        entity.insert(FireworkEmitter::new(trigger.clone()));
                     }
                 }
                 // we need that later
                 let added_entity_id = entity.id();
 
-                if block.get_block_type() != BlockType::Checkpoint {
-                    // TODO: refactor
-                    match self.model_manager.get_handle(&block.get_block_type()) {
-                        Some(handle) => {
-                            entity.insert(Renderable { mesh: handle });
-                        }
-                        None => {
-                            log::warn!("No mesh for block type {:?}", block.get_block_type());
-                        }
-                    }
+                if let Some(handle) = mesh_handle {
+                    entity.insert(Renderable { mesh: handle });
                 }
 
                 if let Some(collider_handle) = collider_handle {
@@ -582,6 +1729,13 @@ impl GameWorld {
                 }
 
                 if block.get_block_type() == BlockType::Player {
+                    let ghost_mesh = self
+                        .model_manager
+                        .get_handle(
+                            &BlockType::Ghost,
+                            &mut *self.world.resource_mut::<GameRng>(),
+                        )
+                        .unwrap();
                     // spawn a ghost following the player
                     self.world.spawn((
                         Position {
@@ -596,9 +1750,7 @@ impl GameWorld {
                             grabbed_scale_factor: position.grabbed_scale_factor,
                             grabbed_rotation: cgmath::Quaternion::one(),
                         },
-                        Renderable {
-                            mesh: self.model_manager.get_handle(&BlockType::Ghost).unwrap(),
-                        },
+                        Renderable { mesh: ghost_mesh },
                         ChargeGhost::new_following(added_entity_id, 1.4, position.position),
                     ));
                 }
@@ -609,6 +1761,9 @@ impl GameWorld {
     }
 
     pub fn move_player(&mut self, direction: cgmath::Vector3<f32>) {
+        if self.replay_player.is_some() {
+            return;
+        }
         self.world
             .get_resource_mut::<Input>()
             .unwrap()
@@ -646,6 +1801,17 @@ impl GameWorld {
     }
 
     pub fn player_grab_action(&mut self) {
+        if self.replay_player.is_some() {
+            return;
+        }
+        let tick_count = self.tick_count();
+        if let Some(replay_recorder) = self.replay_recorder.as_mut() {
+            replay_recorder.record(tick_count, ReplayInput::GrabPressed);
+        }
+        self.grab();
+    }
+
+    fn grab(&mut self) {
         let player_position = self
             .world
             .query_filtered::<&Position, With<Player>>()
@@ -668,6 +1834,13 @@ impl GameWorld {
             .map(|(_, entity, _)| entity)
             .collect::<Vec<_>>();
 
+        // Only boxes are brought back by an undo, enemies are movable as well
+        if query
+            .iter()
+            .any(|entity| self.world.get::<Box>(*entity).is_some())
+        {
+            self.record_box_undo_step();
+        }
         let mut player = self
             .world
             .query_filtered::<&mut Player, With<Player>>()
@@ -704,6 +1877,17 @@ impl GameWorld {
     }
 
     pub fn release_player_grab_action(&mut self) {
+        if self.replay_player.is_some() {
+            return;
+        }
+        let tick_count = self.tick_count();
+        if let Some(replay_recorder) = self.replay_recorder.as_mut() {
+            replay_recorder.record(tick_count, ReplayInput::GrabReleased);
+        }
+        self.release_grab();
+    }
+
+    fn release_grab(&mut self) {
         self.world
             .query_filtered::<&mut Player, With<Player>>()
             .iter_mut(&mut self.world)
@@ -716,4 +1900,926 @@ impl GameWorld {
     pub fn ref_glitch_area_visibility(&self) -> &GlitchAreaVisibility {
         self.world.resource::<GlitchAreaVisibility>()
     }
+
+    /// Returns the position of the player or None if no level is loaded
+    pub fn player_position(&mut self) -> Option<cgmath::Vector3<f32>> {
+        self.world
+            .query_filtered::<&Position, With<Player>>()
+            .iter(&self.world)
+            .next()
+            .map(|position| position.position)
+    }
+
+    /// Returns the charge of the player or None if no level is loaded
+    pub fn player_charge(&mut self) -> Option<f32> {
+        self.world
+            .query::<&Player>()
+            .iter(&self.world)
+            .next()
+            .map(|player| player.charge)
+    }
+
+    /// Returns the condition and whether the door is open for every door in the level
+    pub fn door_states(&mut self) -> Vec<(DoorCondition, bool)> {
+        self.world
+            .query::<&Door>()
+            .iter(&self.world)
+            .map(|door| (door.condition.clone(), door.open))
+            .collect()
+    }
+
+    /// Returns the goal texts of all goals reached since the last call
+    pub fn take_reached_goals(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.reached_goals)
+    }
+
+    /// Calls the callback for every event from now on instead of keeping the events for take_events()
+    pub fn set_event_callback(&mut self, callback: impl FnMut(&GameEvent) + 'static) {
+        self.event_callback = Some(std::boxed::Box::new(callback));
+    }
+
+    /// Returns the events since the last call, always empty if an event callback is set
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn send_event(&mut self, event: GameEvent) {
+        match &mut self.event_callback {
+            Some(callback) => callback(&event),
+            None => self.events.push(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_loader::parse_level;
+    use crate::object_types::Id;
+
+    fn headless_world(level: &str) -> GameWorld {
+        let mut game_world = GameWorld::new_headless();
+        game_world.load_level(parse_level(level).unwrap());
+        game_world
+    }
+
+    #[test]
+    fn test_headless_player_rests_on_floor() {
+        let mut game_world = headless_world("N+P N");
+        game_world.step_ticks(120);
+        assert_eq!(game_world.tick_count(), 120);
+
+        let position = game_world.player_position().unwrap();
+        assert!((position.x - 0.5).abs() < 0.01);
+        assert!(position.z > 1.0 && position.z < 2.0);
+        assert_eq!(game_world.player_charge(), Some(0.0));
+    }
+
+    #[test]
+    fn test_replay_reproduces_recorded_run() {
+        let level = "N+P N N+BF N N\nN N N N N";
+        let mut game_world = headless_world(level);
+        game_world.start_recording();
+        for tick in 0..180 {
+            let direction = if tick < 60 {
+                cgmath::Vector3::new(1.0, 0.0, 0.0)
+            } else if tick < 90 {
+                cgmath::Vector3::new(0.0, -1.0, 0.0)
+            } else {
+                cgmath::Vector3::new(-0.5, 0.5, 0.0)
+            };
+            if tick == 70 {
+                game_world.player_grab_action();
+            }
+            if tick == 150 {
+                game_world.release_player_grab_action();
+            }
+            game_world.move_player(direction);
+            game_world.step_ticks(1);
+        }
+        let recorded_position = game_world.player_position().unwrap();
+        let replay = game_world.stop_recording().unwrap();
+        assert_eq!(replay.ticks, 180);
+
+        let mut replay_world = GameWorld::new_headless();
+        replay_world.play_replay(parse_level(level).unwrap(), replay);
+        // Live input is ignored during playback
+        for _ in 0..180 {
+            replay_world.move_player(cgmath::Vector3::new(0.0, 1.0, 0.0));
+            replay_world.step_ticks(1);
+        }
+        assert_eq!(replay_world.player_position().unwrap(), recorded_position);
+        // The player started at y = -0.5, so the run actually moved it
+        assert!(recorded_position.y > 0.0);
+
+        replay_world.step_ticks(1);
+        assert!(!replay_world.is_playing_replay());
+    }
+
+    #[test]
+    fn test_replay_starts_before_reached_checkpoint() {
+        let level = format!("N+P N+S#cp N N N N\n{}", ["N N N N N N"; 5].join("\n"));
+        let mut game_world = headless_world(&level);
+        game_world.step_ticks(3 * TICKS_PER_SECOND);
+        walk_right(&mut game_world);
+        game_world.step_ticks(1);
+        assert_eq!(game_world.game_status().checkpoint, Some("cp".to_string()));
+
+        // The recording restarts the level at the player block like the playback does
+        game_world.start_recording();
+        assert!((game_world.player_position().unwrap().x - 0.5).abs() < 0.01);
+        walk_right(&mut game_world);
+        let recorded_position = game_world.player_position().unwrap();
+        let replay = game_world.stop_recording().unwrap();
+
+        let mut replay_world = GameWorld::new_headless();
+        replay_world.play_replay(parse_level(&level).unwrap(), replay);
+        replay_world.step_ticks(TICKS_PER_SECOND / 2);
+        assert_eq!(replay_world.player_position().unwrap(), recorded_position);
+    }
+
+    #[test]
+    fn test_headless_charge_pickup() {
+        let mut game_world = headless_world("N+C+P N");
+        game_world.step_ticks(120);
+
+        assert_eq!(game_world.player_charge(), Some(20.0));
+    }
+
+    #[test]
+    fn test_headless_door_opens_on_trigger() {
+        let mut game_world = headless_world("N+T#t+P N+D(#t) N+D(#u) N+T#u");
+        game_world.step_ticks(30);
+
+        let mut door_states = game_world.door_states();
+        door_states.sort_by_key(|(condition, _open)| condition.to_string());
+        assert_eq!(
+            door_states,
+            vec![
+                (DoorCondition::Trigger(Id::try_from("t").unwrap()), true),
+                (DoorCondition::Trigger(Id::try_from("u").unwrap()), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_headless_door_conditions() {
+        let mut game_world =
+            headless_world("N+T#a+P N+T#b N+D(#a&#b) N+D(#a|#b) N+D(!#a) N+D(!(#a&#b))");
+        game_world.step_ticks(30);
+
+        let mut door_states = game_world.door_states();
+        door_states.sort_by_key(|(condition, _open)| condition.to_string());
+        assert_eq!(
+            door_states
+                .iter()
+                .map(|(condition, open)| (condition.to_string(), *open))
+                .collect::<Vec<_>>(),
+            vec![
+                ("!#a".to_string(), false),
+                ("!(#a&#b)".to_string(), true),
+                ("#a&#b".to_string(), false),
+                ("#a|#b".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_headless_platforms_carry_player() {
+        // The elevator lifts the player by one cell per half second
+        let mut game_world = headless_world("N+MZ3+P N");
+        game_world.step_ticks(10);
+        let start = game_world.player_position().unwrap();
+        game_world.step_ticks(30);
+        let lifted = game_world.player_position().unwrap();
+        assert!((lifted.z - start.z - 1.0).abs() < 0.2, "{:?}", lifted);
+
+        // The platform carries the player along the x axis
+        let mut game_world = headless_world("N+MX3+P N N N");
+        game_world.step_ticks(10);
+        let start = game_world.player_position().unwrap();
+        game_world.step_ticks(30);
+        let carried = game_world.player_position().unwrap();
+        assert!((carried.x - start.x - 1.0).abs() < 0.2, "{:?}", carried);
+
+        // The platform passes its waypoints one after another, rows go into the negative y direction
+        let mut game_world = headless_world("N+MX1Y1+P N\nN N");
+        game_world.step_ticks(30);
+        let first_waypoint = game_world.player_position().unwrap();
+        assert!((first_waypoint.x - 1.5).abs() < 0.2, "{:?}", first_waypoint);
+        assert!((first_waypoint.y + 0.5).abs() < 0.2, "{:?}", first_waypoint);
+        game_world.step_ticks(30);
+        let second_waypoint = game_world.player_position().unwrap();
+        assert!(
+            (second_waypoint.x - 1.5).abs() < 0.2,
+            "{:?}",
+            second_waypoint
+        );
+        assert!(
+            (second_waypoint.y + 1.5).abs() < 0.2,
+            "{:?}",
+            second_waypoint
+        );
+
+        // A platform waiting for a trigger does not move
+        let mut game_world = headless_world("N+MZ3(#t)+P N+T#t");
+        game_world.step_ticks(10);
+        let start = game_world.player_position().unwrap();
+        game_world.step_ticks(30);
+        let waiting = game_world.player_position().unwrap();
+        assert!((waiting.z - start.z).abs() < 0.05, "{:?}", waiting);
+    }
+
+    #[test]
+    fn test_headless_teleporter_pairs() {
+        // The player arrives at the partner and does not bounce back
+        let mut game_world = headless_world("N+O(#a)+P N N N+O(#a)");
+        game_world.step_ticks(10);
+        let arrived = game_world.player_position().unwrap();
+        assert!((arrived.x - 3.5).abs() < 0.1, "{:?}", arrived);
+        game_world.step_ticks(120);
+        let stayed = game_world.player_position().unwrap();
+        assert!((stayed.x - 3.5).abs() < 0.1, "{:?}", stayed);
+
+        // Boxes only travel while the player pulls them
+        let mut game_world = headless_world("N+O(#a)+BF N N+P N+O(#a)");
+        game_world.step_ticks(60);
+        let box_x = game_world
+            .world
+            .query_filtered::<&Position, With<Box>>()
+            .single(&game_world.world)
+            .position
+            .x;
+        assert!((box_x - 0.5).abs() < 0.1, "{}", box_x);
+    }
+
+    #[test]
+    fn test_headless_patrol_enemy_follows_waypoints() {
+        let enemy_position = |game_world: &mut GameWorld| {
+            game_world
+                .world
+                .query_filtered::<&Position, With<PatrolEnemy>>()
+                .single(&game_world.world)
+                .position
+        };
+
+        // The player stays out of reach so that the level is not reset
+        // A ping-pong enemy walks to the end of its path and comes back
+        let mut game_world = headless_world("N N+E3P2(X2) N N\nN N N N\nN+P N N N");
+        let start = enemy_position(&mut game_world);
+        game_world.step_ticks(TICKS_PER_SECOND);
+        let middle = enemy_position(&mut game_world);
+        assert!((middle.x - start.x - 2.0).abs() < 0.3, "{:?}", middle);
+        game_world.step_ticks(TICKS_PER_SECOND);
+        let back = enemy_position(&mut game_world);
+        assert!((back.x - start.x).abs() < 0.3, "{:?}", back);
+
+        // A looping enemy walks around its square and passes every corner
+        let mut game_world = headless_world("N+E3L4(X1,Y1,X-1,Y-1) N N\nN N N\nN N N\nN N N+P");
+        let start = enemy_position(&mut game_world);
+        let mut positions = Vec::new();
+        for _ in 0..TICKS_PER_SECOND * 2 {
+            game_world.step_ticks(1);
+            positions.push(enemy_position(&mut game_world));
+        }
+        for corner in [(1.0, 0.0), (1.0, -1.0), (0.0, -1.0), (0.0, 0.0)] {
+            assert!(
+                positions.iter().any(|position| {
+                    (position.x - start.x - corner.0).abs() < 0.25
+                        && (position.y - start.y - corner.1).abs() < 0.25
+                }),
+                "{:?}",
+                corner
+            );
+        }
+    }
+
+    #[test]
+    fn test_headless_chasing_enemy() {
+        let start_chase = |level: &str| {
+            let mut game_world = headless_world(level);
+            // Enough charge to stay in the glitch area
+            game_world
+                .world
+                .query::<&mut Player>()
+                .single_mut(&mut game_world.world)
+                .charge = 100.0;
+            game_world.step_ticks(TICKS_PER_SECOND / 2);
+            game_world
+                .world
+                .query_filtered::<&Position, With<ChasingEnemy>>()
+                .single(&game_world.world)
+                .position
+        };
+
+        // The enemy walks towards the player in the glitch area
+        let enemy = start_chase("_N+E4 _N _N _N+P");
+        assert!(enemy.x > 1.5, "{:?}", enemy);
+
+        // The enemy ignores the player outside of the glitch area
+        let enemy = start_chase("_N+E4 _N _N N+P");
+        assert!((enemy.x - 0.5).abs() < 0.1, "{:?}", enemy);
+    }
+
+    #[test]
+    fn test_headless_enemy_params() {
+        let enemy_x = |level: &str| {
+            let mut game_world = headless_world(level);
+            game_world.step_ticks(TICKS_PER_SECOND);
+            game_world
+                .world
+                .query_filtered::<&Position, With<LinearEnemy>>()
+                .single(&game_world.world)
+                .position
+                .x
+        };
+
+        // The default speed is 4 cells per second
+        let slow = enemy_x("N+E2X{speed=1} N N N N N N\nN N N N N N N+P");
+        let fast = enemy_x("N+E2X N N N N N N\nN N N N N N N+P");
+        assert!((slow - 1.5).abs() < 0.3, "{}", slow);
+        assert!(fast > 3.5, "{}", fast);
+
+        // A backward enemy starts to move into the negative x direction
+        let backward = enemy_x("N N N N N N+E2X{start=backward,speed=1}\nN+P N N N N N");
+        assert!((backward - 4.5).abs() < 0.3, "{}", backward);
+
+        // Patrol and chasing enemies hit the player as set in their properties
+        let mut game_world = headless_world("N+P N N+E3P(X1){damage=5} N N+E4{knockback=8}");
+        let patrol_damage = game_world
+            .world
+            .query_filtered::<&DamageArea, With<PatrolEnemy>>()
+            .single(&game_world.world)
+            .damage;
+        let chasing_knockback = game_world
+            .world
+            .query_filtered::<&DamageArea, With<ChasingEnemy>>()
+            .single(&game_world.world)
+            .knockback;
+        assert_eq!(patrol_damage, 5.0);
+        assert_eq!(chasing_knockback, 8.0);
+    }
+
+    #[test]
+    fn test_headless_game_rules() {
+        // The header of the level overrides the default rules
+        let mut game_world = headless_world("charge_depletion: 30\ngravity: -2\n\n_N+P _N");
+        let game_rules = game_world.game_rules();
+        assert_eq!(game_rules.charge_depletion, 30.0);
+        assert_eq!(game_rules.gravity, -2.0);
+        assert_eq!(game_rules.player_speed, GameRules::default().player_speed);
+
+        game_world
+            .world
+            .query::<&mut Player>()
+            .single_mut(&mut game_world.world)
+            .charge = 100.0;
+        game_world.step_ticks(TICKS_PER_SECOND);
+        let charge = game_world.player_charge().unwrap();
+        assert!((charge - 70.0).abs() < 0.1, "{}", charge);
+
+        // Configured rules apply unless the header changes them
+        game_world.set_game_rules(GameRules {
+            player_speed: 3.0,
+            gravity: -20.0,
+            ..Default::default()
+        });
+        let game_rules = game_world.game_rules();
+        assert_eq!(game_rules.player_speed, 3.0);
+        assert_eq!(game_rules.gravity, -2.0);
+
+        // The player falls slower with less gravity
+        let height_after_falling = |level: &str| {
+            let mut game_world = headless_world(level);
+            game_world.step_ticks(TICKS_PER_SECOND / 4);
+            game_world.player_position().unwrap().z
+        };
+        let low_gravity = height_after_falling("gravity: -2\n\nN+X+X+P");
+        let default_gravity = height_after_falling("N+X+X+P");
+        assert!(low_gravity > default_gravity + 0.1);
+    }
+
+    #[test]
+    fn test_headless_game_status() {
+        let mut game_world = GameWorld::new_headless();
+        assert!(!game_world.game_status().level_loaded);
+
+        game_world.load_level(parse_level("par_time: 30\n\n_N+S#cp+P N").unwrap());
+        game_world
+            .world
+            .query::<&mut Player>()
+            .single_mut(&mut game_world.world)
+            .charge = 100.0;
+        game_world.step_ticks(TICKS_PER_SECOND);
+
+        let status = game_world.game_status().clone();
+        assert!(status.level_loaded);
+        assert!(status.in_glitch_area);
+        assert!((status.charge - 99.0).abs() < 0.1, "{}", status.charge);
+        assert_eq!(status.max_charge, MAX_PLAYER_CHARGE);
+        assert_eq!(status.checkpoint, Some("cp".to_string()));
+        assert!((status.elapsed_time - 1.0).abs() < 0.01);
+        assert_eq!(status.par_time, Some(30.0));
+
+        // Loading a level restarts the timer and forgets the checkpoint
+        game_world.load_level(parse_level("N+P N").unwrap());
+        game_world.step_ticks(1);
+        let status = game_world.game_status();
+        assert!(!status.in_glitch_area);
+        assert_eq!(status.checkpoint, None);
+        assert!(status.elapsed_time < 0.1);
+    }
+
+    #[test]
+    fn test_headless_game_events() {
+        let mut game_world = headless_world("title: Events\n\nN+T#t+P N+D(#t)#door");
+        game_world.step_ticks(30);
+        assert_eq!(
+            game_world.take_events(),
+            vec![
+                GameEvent::LevelLoaded {
+                    title: Some("Events".to_string())
+                },
+                GameEvent::DoorOpened {
+                    id: "door".to_string()
+                },
+            ]
+        );
+        assert!(game_world.take_events().is_empty());
+
+        // The checkpoint is only reported when it changes, not every tick the player stands on it
+        let mut game_world = headless_world("N+S#cp+P N");
+        game_world.take_events();
+        game_world.step_ticks(30);
+        assert_eq!(
+            game_world.take_events(),
+            vec![GameEvent::CheckpointSet {
+                id: "cp".to_string()
+            }]
+        );
+
+        // Without a floor the player falls and dies once before the level is reset
+        let mut game_world = headless_world("P");
+        game_world.take_events();
+        game_world.step_ticks(2 * TICKS_PER_SECOND);
+        let events = game_world.take_events();
+        assert_eq!(
+            events[0],
+            GameEvent::PlayerDied {
+                cause: DeathCause::Fell
+            }
+        );
+    }
+
+    #[test]
+    fn test_headless_game_event_callback() {
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut game_world = GameWorld::new_headless();
+        let callback_events = events.clone();
+        game_world
+            .set_event_callback(move |event| callback_events.borrow_mut().push(event.clone()));
+        game_world.load_level(parse_level("N+P N+S#cp").unwrap());
+
+        assert_eq!(
+            *events.borrow(),
+            vec![GameEvent::LevelLoaded { title: None }]
+        );
+        assert!(game_world.take_events().is_empty());
+    }
+
+    fn level_pack(levels: &[(&str, &str)]) -> LevelPack {
+        let levels = levels
+            .iter()
+            .map(|(id, level)| format!("{{ \"id\": \"{}\", \"level\": \"{}\" }}", id, level))
+            .collect::<Vec<_>>();
+        LevelPack::from_json(&format!(
+            "{{ \"version\": 1, \"title\": null, \"author\": null, \"levels\": [{}] }}",
+            levels.join(", ")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_headless_level_pack_progression() {
+        let mut game_world = GameWorld::new_headless();
+        game_world
+            .load_level_pack(level_pack(&[
+                ("a", "N+G(ONE){level=#c}+P N"),
+                ("b", "N+P"),
+                ("c", "N N+G(TWO)+P"),
+            ]))
+            .unwrap();
+        assert_eq!(
+            game_world.level_pack_progress().unwrap().unlocked,
+            vec!["a".to_string()]
+        );
+        assert_eq!(
+            game_world.load_pack_level("b"),
+            Err("Level #b is locked".to_string())
+        );
+
+        // The goal leads to the level named in its properties and skips b
+        game_world.step_ticks(3);
+        assert_eq!(game_world.game_status().pack_level, Some("c".to_string()));
+        let progress = game_world.level_pack_progress().unwrap();
+        assert_eq!(progress.unlocked, vec!["a".to_string(), "c".to_string()]);
+        assert_eq!(progress.completed, vec!["a".to_string()]);
+        assert!((game_world.player_position().unwrap().x - 1.5).abs() < 0.01);
+
+        // After the last level the goal text is shown
+        game_world.step_ticks(1);
+        assert_eq!(game_world.game_status().pack_level, None);
+        assert_eq!(
+            game_world.level_pack_progress().unwrap().completed,
+            vec!["a".to_string(), "c".to_string()]
+        );
+        assert_eq!(
+            game_world.take_reached_goals(),
+            vec!["ONE".to_string(), "TWO".to_string()]
+        );
+
+        // Completed levels can be played again
+        assert_eq!(game_world.load_pack_level("a"), Ok(()));
+        game_world.step_ticks(3);
+        assert_eq!(game_world.game_status().pack_level, Some("c".to_string()));
+    }
+
+    #[test]
+    fn test_headless_level_pack_goal_leads_to_next_level() {
+        let mut game_world = GameWorld::new_headless();
+        game_world
+            .load_level_pack(level_pack(&[("a", "N+G(ONE)+P N"), ("b", "N N N+P")]))
+            .unwrap();
+        game_world.step_ticks(10);
+
+        assert_eq!(game_world.game_status().pack_level, Some("b".to_string()));
+        assert!((game_world.player_position().unwrap().x - 2.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_headless_level_pack_with_empty_imported_progress() {
+        let pack = level_pack(&[("a", "N+P"), ("b", "N N+P")]);
+        let mut save_state = SaveState::from_json_string(&format!(
+            "{{ \"version\": 1, \"settings\": {{}}, \"levels\": {{}}, \"level_packs\": {{ \"{}\": {{ \"unlocked\": [], \"completed\": [] }} }} }}",
+            pack.key()
+        ))
+        .unwrap();
+        let mut game_world = GameWorld::new_headless();
+        game_world.set_save_state(save_state.clone());
+
+        // The first level is unlocked even if the imported progress lacks it
+        assert_eq!(game_world.load_level_pack(pack.clone()), Ok(()));
+        game_world.step_ticks(1);
+        assert_eq!(game_world.game_status().pack_level, Some("a".to_string()));
+        assert_eq!(
+            game_world.level_pack_progress().unwrap().unlocked,
+            vec!["a".to_string()]
+        );
+
+        // ... also when the save state is imported while the pack is loaded
+        save_state
+            .level_packs
+            .get_mut(&pack.key())
+            .unwrap()
+            .unlocked
+            .clear();
+        game_world.set_save_state(save_state);
+        assert_eq!(game_world.load_pack_level("a"), Ok(()));
+    }
+
+    #[test]
+    fn test_headless_save_state_resumes_at_checkpoint() {
+        let level = "N+S#cp+P N";
+        let level_key = content_key(&parse_level(level).unwrap().to_level_string());
+        let mut game_world = headless_world(level);
+        game_world.take_save_state_changed();
+        game_world.step_ticks(10);
+        assert!(game_world.take_save_state_changed());
+        assert_eq!(game_world.save_state().checkpoint(&level_key), Some("cp"));
+        // Standing on the checkpoint does not change the save state again
+        game_world.step_ticks(10);
+        assert!(!game_world.take_save_state_changed());
+
+        // The next session starts at the checkpoint
+        let level = "N+P N N+S#cp";
+        let mut save_state = SaveState::default();
+        save_state.set_checkpoint(
+            &content_key(&parse_level(level).unwrap().to_level_string()),
+            "cp".to_string(),
+        );
+        let mut game_world = GameWorld::new_headless();
+        game_world.set_save_state(save_state);
+        game_world.load_level(parse_level(level).unwrap());
+        game_world.step_ticks(1);
+        assert_eq!(game_world.game_status().checkpoint, Some("cp".to_string()));
+        assert!((game_world.player_position().unwrap().x - 2.5).abs() < 0.01);
+
+        // Replays always start at the beginning of the level
+        game_world.play_replay(
+            parse_level(level).unwrap(),
+            Replay {
+                version: crate::replay::REPLAY_VERSION,
+                seed: DEFAULT_SEED,
+                ticks: 1,
+                events: Vec::new(),
+            },
+        );
+        game_world.step_ticks(1);
+        assert!((game_world.player_position().unwrap().x - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_headless_save_state_records_completed_levels() {
+        let level = "N+G(END)+P N";
+        let level_key = content_key(&parse_level(level).unwrap().to_level_string());
+        let mut game_world = GameWorld::new_headless();
+        game_world.set_eye_distance_setting(-2.0);
+        game_world.load_level(parse_level(level).unwrap());
+        game_world.step_ticks(10);
+
+        let save_state = game_world.save_state();
+        assert_eq!(save_state.settings.eye_distance, Some(-2.0));
+        assert!(save_state.levels[&level_key].completed);
+        let best_time = save_state.levels[&level_key].best_time.unwrap();
+        assert!(best_time > 0.0 && best_time < 0.2, "{}", best_time);
+    }
+
+    // Walks to the right for half a second and returns where the player ended up
+    fn walk_right(game_world: &mut GameWorld) -> cgmath::Vector3<f32> {
+        for _ in 0..TICKS_PER_SECOND / 2 {
+            game_world.move_player(cgmath::Vector3::new(1.0, 0.0, 0.0));
+            game_world.step_ticks(1);
+        }
+        game_world.player_position().unwrap()
+    }
+
+    #[test]
+    fn test_headless_snapshot_restores_world() {
+        let mut game_world = headless_world(&format!(
+            "N+P N N+BF N N N\n{}",
+            ["N N N N N N"; 5].join("\n")
+        ));
+        // Let the camera settle, the player moves relative to it
+        game_world.step_ticks(3 * TICKS_PER_SECOND);
+        let snapshot = game_world.take_snapshot().unwrap();
+        let start_position = game_world.player_position().unwrap();
+
+        let first_run = walk_right(&mut game_world);
+        assert!((first_run - start_position).magnitude() > 1.0);
+
+        game_world.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(game_world.player_position().unwrap(), start_position);
+        let second_run = walk_right(&mut game_world);
+        assert!(
+            (first_run - second_run).magnitude() < 0.01,
+            "{:?} {:?}",
+            first_run,
+            second_run
+        );
+
+        // After a reset the entities are matched by their blocks
+        game_world.reset_level();
+        game_world.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(game_world.player_position().unwrap(), start_position);
+
+        game_world.load_level(parse_level("N+P N").unwrap());
+        assert_eq!(
+            game_world.restore_snapshot(&snapshot),
+            Err("The snapshot was taken in another level".to_string())
+        );
+    }
+
+    #[test]
+    fn test_headless_quick_save() {
+        let mut game_world = headless_world("N+P N N+C N\nN N N N\nN N N N");
+        game_world.step_ticks(10);
+        game_world.quick_save();
+        let position = game_world.player_position().unwrap();
+
+        walk_right(&mut game_world);
+        game_world
+            .world
+            .query::<&mut Player>()
+            .single_mut(&mut game_world.world)
+            .charge = 50.0;
+        game_world.quick_load();
+        assert_eq!(game_world.player_position().unwrap(), position);
+        assert_eq!(game_world.player_charge(), Some(0.0));
+
+        // Loading a level drops the quick save
+        game_world.load_level(parse_level("N N+P").unwrap());
+        game_world.quick_load();
+        assert!((game_world.player_position().unwrap().x - 1.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_headless_quick_save_restores_moving_objects() {
+        let moving_positions = |game_world: &mut GameWorld| {
+            let platform = game_world
+                .world
+                .query_filtered::<&Position, With<Platform>>()
+                .single(&game_world.world)
+                .position;
+            let enemy = game_world
+                .world
+                .query_filtered::<&Position, With<LinearEnemy>>()
+                .single(&game_world.world)
+                .position;
+            (platform, enemy)
+        };
+        let assert_moving_positions =
+            |actual: (Vector3<f32>, Vector3<f32>), expected: (Vector3<f32>, Vector3<f32>)| {
+                assert!(
+                    (actual.0 - expected.0).magnitude() < 0.01,
+                    "{:?} {:?}",
+                    actual.0,
+                    expected.0
+                );
+                assert!(
+                    (actual.1 - expected.1).magnitude() < 0.01,
+                    "{:?} {:?}",
+                    actual.1,
+                    expected.1
+                );
+            };
+
+        let mut game_world = headless_world("N+MX2 N N N N\nN+W N+E2X N N N+W\nN N N N N+P");
+        game_world.step_ticks(10);
+        game_world.quick_save();
+        game_world.step_ticks(1);
+        let first_tick = moving_positions(&mut game_world);
+        // The enemy only changes its direction after the physics step of a tick
+        game_world.step_ticks(10);
+        let later_tick = moving_positions(&mut game_world);
+
+        // The platform reaches its end and the enemy turns around at the wall
+        game_world.step_ticks(TICKS_PER_SECOND);
+        game_world.quick_load();
+        game_world.step_ticks(1);
+        assert_moving_positions(moving_positions(&mut game_world), first_tick);
+        game_world.step_ticks(10);
+        assert_moving_positions(moving_positions(&mut game_world), later_tick);
+    }
+
+    #[test]
+    fn test_headless_rewind() {
+        let mut game_world = headless_world(&format!(
+            "N+P N N+BF N N N\n{}",
+            ["N N N N N N"; 5].join("\n")
+        ));
+        game_world.step_ticks(3 * TICKS_PER_SECOND);
+        let start_position = game_world.player_position().unwrap();
+        let first_run = walk_right(&mut game_world);
+
+        // The first rewound tick restores the state after the last tick
+        game_world.start_rewind();
+        game_world.step_ticks(TICKS_PER_SECOND / 2 + 1);
+        assert!(game_world.game_status().rewinding);
+        assert_eq!(game_world.player_position().unwrap(), start_position);
+
+        // The simulation resumes from the rewound state
+        game_world.stop_rewind();
+        let second_run = walk_right(&mut game_world);
+        assert!(
+            (first_run - second_run).magnitude() < 0.01,
+            "{:?} {:?}",
+            first_run,
+            second_run
+        );
+
+        // Rewinding is not possible while recording
+        game_world.start_recording();
+        game_world.start_rewind();
+        assert!(!game_world.is_rewinding());
+    }
+
+    #[test]
+    fn test_headless_rewind_platform() {
+        let platform_position = |game_world: &mut GameWorld| {
+            game_world
+                .world
+                .query_filtered::<&Position, With<Platform>>()
+                .single(&game_world.world)
+                .position
+        };
+
+        let mut game_world = headless_world("N+MX2 N N N\nN N N N\nN N N N+P");
+        game_world.step_ticks(10);
+        let start_position = platform_position(&mut game_world);
+        game_world.step_ticks(10);
+        let later_position = platform_position(&mut game_world);
+
+        // The platform turns around at its end and is rewound to the start
+        game_world.step_ticks(TICKS_PER_SECOND);
+        game_world.start_rewind();
+        game_world.step_ticks(TICKS_PER_SECOND + 11);
+        game_world.stop_rewind();
+        assert_eq!(platform_position(&mut game_world), start_position);
+        game_world.step_ticks(10);
+        let position = platform_position(&mut game_world);
+        assert!(
+            (position - later_position).magnitude() < 0.01,
+            "{:?} {:?}",
+            position,
+            later_position
+        );
+
+        // Rewinding does not go back across a reset of the level
+        game_world.reset_level();
+        assert!(game_world.rewind_buffer.is_empty());
+    }
+
+    fn box_position(game_world: &mut GameWorld) -> cgmath::Vector3<f32> {
+        game_world
+            .world
+            .query_filtered::<&Position, With<Box>>()
+            .single(&game_world.world)
+            .position
+    }
+
+    #[test]
+    fn test_headless_undo_box_push() {
+        let mut game_world = headless_world(&format!(
+            "N+P N+BX N N N N\n{}",
+            ["N N N N N N"; 5].join("\n")
+        ));
+        game_world.step_ticks(3 * TICKS_PER_SECOND);
+        let start_position = box_position(&mut game_world);
+
+        // Grabbing again without moving the box is no further step
+        game_world.player_grab_action();
+        game_world.release_player_grab_action();
+        game_world.player_grab_action();
+        walk_right(&mut game_world);
+        game_world.release_player_grab_action();
+        let pushed_position = box_position(&mut game_world);
+        assert!((pushed_position - start_position).magnitude() > 0.5);
+        assert_eq!(game_world.game_status().box_undo_steps, 1);
+
+        // A grab out of reach of any box is no step to undo
+        walk_right(&mut game_world);
+        game_world.player_grab_action();
+        game_world.release_player_grab_action();
+
+        game_world.undo_box_push();
+        assert_eq!(box_position(&mut game_world), start_position);
+        game_world.step_ticks(1);
+        assert!((box_position(&mut game_world) - start_position).magnitude() < 0.01);
+        assert_eq!(game_world.game_status().box_undo_steps, 0);
+    }
+
+    #[test]
+    fn test_headless_undo_box_push_keeps_enemies() {
+        let enemy_position = |game_world: &mut GameWorld| {
+            game_world
+                .world
+                .query_filtered::<&Position, With<LinearEnemy>>()
+                .single(&game_world.world)
+                .position
+        };
+
+        let mut game_world = headless_world(&format!(
+            "N+P N+BX N N N N\n{}\nN+W N+E2X N N N N+W",
+            ["N N N N N N"; 4].join("\n")
+        ));
+        game_world.step_ticks(3 * TICKS_PER_SECOND);
+        game_world.player_grab_action();
+        walk_right(&mut game_world);
+        game_world.release_player_grab_action();
+
+        let position = enemy_position(&mut game_world);
+        game_world.undo_box_push();
+        assert_eq!(enemy_position(&mut game_world), position);
+
+        // A grab with only an enemy in reach is no step to undo
+        let mut game_world = headless_world(&format!(
+            "N+W N+P N+E2X N N N+W\n{}\nN N N N N N+BX",
+            ["N N N N N N"; 4].join("\n")
+        ));
+        game_world.step_ticks(10);
+        game_world.player_grab_action();
+        game_world.release_player_grab_action();
+        game_world.step_ticks(1);
+        assert_eq!(game_world.game_status().box_undo_steps, 0);
+    }
+
+    #[test]
+    fn test_headless_goal_reached() {
+        let mut game_world = headless_world("N+G(END)+P N");
+        game_world.step_ticks(10);
+
+        assert_eq!(game_world.take_reached_goals(), vec!["END".to_string()]);
+        assert!(game_world.take_reached_goals().is_empty());
+    }
+
+    #[test]
+    fn test_headless_goal_loads_next_level_from_metadata() {
+        let next = crate::level_compressor::compress_level("N N N+P");
+        let mut game_world = headless_world(&format!("next: {}\nN+G(END)+P N", next));
+        game_world.step_ticks(10);
+
+        assert_eq!(game_world.take_reached_goals(), vec!["END".to_string()]);
+        let position = game_world.player_position().unwrap();
+        assert!((position.x - 2.5).abs() < 0.01);
+    }
 }
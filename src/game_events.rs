@@ -0,0 +1,39 @@
+use bevy_ecs::system::Resource;
+use serde::{Deserialize, Serialize};
+
+// Why the player died
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    // The player fell off the level
+    Fell,
+    // The charge ran out in the glitch area or an enemy took the rest of it
+    ChargeDepleted,
+}
+
+// Important moments of the game the host (web page or native app) can react to,
+// e.g. to show messages, play sounds or track analytics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    LevelLoaded { title: Option<String> },
+    CheckpointSet { id: String },
+    PlayerDied { cause: DeathCause },
+    // Blocks without an ID in the level text have a generated one (see Id::generated)
+    DoorOpened { id: String },
+    GoalReached { text: String },
+}
+
+// The events sent by the systems during a tick, GameWorld collects them after every tick
+#[derive(Resource, Default)]
+pub struct GameEvents {
+    events: Vec<GameEvent>,
+}
+
+impl GameEvents {
+    pub fn send(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    pub fn drain(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
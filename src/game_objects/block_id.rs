@@ -0,0 +1,8 @@
+use bevy_ecs::component::Component;
+
+use crate::object_types::Id;
+
+// The ID of the level block the entity was spawned for, generated if the level text gives none
+// Unlike the entity it stays the same when the level is reset
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockId(pub Id);
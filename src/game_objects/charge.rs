@@ -8,7 +8,8 @@ use cgmath::{InnerSpace, One, Rotation3, Vector3, VectorSpace, Vector4};
 use crate::stereo_camera::StereoCamera;
 
 use super::{
-    constants::TICKS_PER_SECOND,
+    constants::{MAX_PLAYER_CHARGE, TICKS_PER_SECOND},
+    game_rules::GameRules,
     glitch_area::GlitchAreaVisibility,
     player::Player,
     position::{self, Position},
@@ -19,7 +20,8 @@ use super::{
 
 #[derive(Component)]
 pub struct ChargeSpawnArea {
-    cooldown_left: f32,
+    // Seconds until the area gives charge again
+    pub cooldown_left: f32,
 
     spawned_ghost: Option<Entity>,
 }
@@ -172,7 +174,7 @@ pub fn move_charge_ghost_system(
         let is_in_following_mode = ghost.follow_entity.is_some();
         let max_scale = 0.7;
         let min_scale = if is_in_following_mode { 0.2 } else { 0.0 };
-        let max_charge = 100.0;
+        let max_charge = MAX_PLAYER_CHARGE;
         ghost_position.scale = cgmath::Vector3::new(1.0, 1.0, 1.0)
             * ((ghost.animation_charge_value / max_charge).sqrt() * max_scale).max(min_scale);
 
@@ -192,6 +194,7 @@ pub fn move_charge_ghost_system(
 pub fn charge_recharge_system(
     mut commands: Commands,
     mut time_keeper: ResMut<TimeKeeper>,
+    game_rules: Res<GameRules>,
     mut query: Query<(&mut ChargeSpawnArea, &Sensor, Entity, &Position)>,
     mut player_query: Query<&mut Player>,
     mut ghost_query: Query<&mut ChargeGhost>,
@@ -203,7 +206,7 @@ pub fn charge_recharge_system(
         return;
     }
 
-    let charge_added = 20.0;
+    let charge_added = game_rules.recharge_amount;
 
     for (mut charge, sensor, sensor_entity, position) in &mut query {
         let triggering_player_entity = sensor
@@ -217,7 +220,7 @@ pub fn charge_recharge_system(
         if triggered_by_player && can_recharge {
             for player_entity in triggering_player_entity {
                 if let Ok(mut player) = player_query.get_mut(*player_entity) {
-                    player.charge = (player.charge.max(0.0) + charge_added).min(100.0);
+                    player.charge = (player.charge.max(0.0) + charge_added).min(MAX_PLAYER_CHARGE);
                     if let Some(Ok(mut ghost)) =
                         charge.spawned_ghost.map(|g| ghost_query.get_mut(g))
                     {
@@ -227,7 +230,7 @@ pub fn charge_recharge_system(
                 }
             }
 
-            charge.cooldown_left = 10.0;
+            charge.cooldown_left = game_rules.recharge_cooldown;
         } else {
             charge.cooldown_left -= 1.0 / TICKS_PER_SECOND as f32;
         }
@@ -260,6 +263,7 @@ pub fn charge_recharge_system(
 // If the charge reaches 0 the player dies
 pub fn player_charge_depletion_system(
     mut time_keeper: ResMut<TimeKeeper>,
+    game_rules: Res<GameRules>,
     mut player_query: Query<(&mut Player, &Position)>,
     mut glitch_area_visibility: ResMut<GlitchAreaVisibility>,
     mut stereo_camera: ResMut<StereoCamera>,
@@ -270,8 +274,7 @@ pub fn player_charge_depletion_system(
         return;
     }
 
-    let deplete_per_second = 1.0;
-    let deplete_per_tick = deplete_per_second / TICKS_PER_SECOND as f32;
+    let deplete_per_tick = game_rules.charge_depletion / TICKS_PER_SECOND as f32;
 
     for (mut player, pos) in &mut player_query {
         let player_x_y_cell = pos.get_cell();
@@ -10,7 +10,7 @@ use rand::Rng;
 use crate::object_types::{Id, BlockType};
 
 use super::{
-    constants::TICKS_PER_SECOND, game_system_commands::GameSystemCommands, player::Player,
+    constants::TICKS_PER_SECOND, game_rng::GameRng, game_system_commands::GameSystemCommands, player::Player,
     position::Position, sensor::Sensor, time_keeper::TimeKeeper, model_manager, renderable::Renderable,
 };
 
@@ -64,6 +64,7 @@ pub fn spawn_checkpoint_particle_system(
     mut commands: Commands,
     time: Res<TimeKeeper>,
     model_manager: Res<model_manager::ModelManager>,
+    mut rng: ResMut<GameRng>,
     mut query: Query<(&mut Checkpoint, &Position)>,
 ) {
     if !time.is_in_fixed_tick() {
@@ -78,7 +79,6 @@ pub fn spawn_checkpoint_particle_system(
 
         checkpoint.spawn_cool_down = 0.2;
 
-        let mut rng = rand::thread_rng();
         let mut pos = Position::default();
         pos.position = position.position;
         pos.position.x = pos.position.x + rng.gen::<f32>() * 1.0 - 0.5;
@@ -96,7 +96,7 @@ pub fn spawn_checkpoint_particle_system(
                 start_z: pos.position.z,
             },
             pos,
-            Renderable { mesh: model_manager.get_handle(&BlockType::Checkpoint).unwrap() },
+            Renderable { mesh: model_manager.get_handle(&BlockType::Checkpoint, &mut *rng).unwrap() },
         ));
     }
 }
@@ -1 +1,12 @@
 pub const TICKS_PER_SECOND: u32 = 60;
+// The maximum number of fixed ticks simulated in a single frame
+// If a frame takes longer, the remaining time is dropped to avoid a spiral of death
+pub const MAX_FIXED_TICKS_PER_FRAME: u32 = 5;
+// The charge of a fully charged player
+pub const MAX_PLAYER_CHARGE: f32 = 100.0;
+// The seed of the game RNG if none is set explicitly
+pub const DEFAULT_SEED: u64 = 0;
+// The memory the snapshots kept for rewinding may take, older snapshots are dropped beyond it
+pub const REWIND_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+// The number of box pushes that can be undone, older ones are forgotten
+pub const MAX_BOX_UNDO_STEPS: usize = 32;
@@ -1,16 +1,21 @@
 use bevy_ecs::{
     component::Component,
     entity::Entity,
-    system::{Commands, Query, Res},
+    system::{Commands, Query, Res, ResMut},
 };
 use cgmath::InnerSpace;
 use rand::Rng;
 
-use crate::model;
+use crate::object_types::Id;
 
 use super::{
-    model_manager::ModelManager, position::Position, renderable::Renderable,
+    game_rng::GameRng,
+    model_manager::ModelManager,
+    position::Position,
+    renderable::Renderable,
+    sensor::Sensor,
     time_keeper::TimeKeeper,
+    trigger::{active_trigger_ids, Trigger},
 };
 
 #[derive(Component)]
@@ -61,6 +66,8 @@ impl FireworkParticle {
 #[derive(Component)]
 pub struct FireworkEmitter {
     countdown: f32,
+    // If set fireworks are only launched while this trigger is active
+    trigger: Option<Id>,
 }
 
 pub fn firework_particle_system(
@@ -86,8 +93,11 @@ pub fn firework_particle_system(
 }
 
 impl FireworkEmitter {
-    pub fn new() -> Self {
-        Self { countdown: 0.0 }
+    pub fn new(trigger: Option<Id>) -> Self {
+        Self {
+            countdown: 0.0,
+            trigger,
+        }
     }
 
     pub fn update(&mut self, dt: f32) {
@@ -98,22 +108,29 @@ impl FireworkEmitter {
         self.countdown <= 0.0
     }
 
-    pub fn reset(&mut self) {
-        self.countdown = rand::thread_rng().gen_range(0.2..10.0);
+    pub fn reset(&mut self, rng: &mut impl Rng) {
+        self.countdown = rng.gen_range(0.2..10.0);
     }
 }
 
 pub fn firework_emitter_system(
     time_keeper: Res<TimeKeeper>,
     model_manager: Res<ModelManager>,
+    mut rng: ResMut<GameRng>,
     mut commands: Commands,
     mut firework_emitters: Query<(&mut FireworkEmitter, &Position)>,
+    trigger_query: Query<(&Sensor, &Trigger)>,
 ) {
+    let active_trigger_ids = active_trigger_ids(&trigger_query);
     for (mut emitter, position) in firework_emitters.iter_mut() {
+        if let Some(trigger) = &emitter.trigger {
+            if !active_trigger_ids.contains(trigger) {
+                continue;
+            }
+        }
         emitter.update(time_keeper.delta_seconds());
         if emitter.is_ready() {
-            emitter.reset();
-            let mut rng = rand::thread_rng();
+            emitter.reset(&mut *rng);
 
             let random_center = cgmath::Vector3::new(
                 rng.gen_range(-0.5..0.5),
@@ -165,7 +182,7 @@ pub fn firework_emitter_system(
                         pos,
                         Renderable {
                             mesh: model_manager
-                                .get_handle(&crate::object_types::BlockType::Cube)
+                                .get_handle(&crate::object_types::BlockType::Cube, &mut *rng)
                                 .unwrap(),
                         },
                     ));
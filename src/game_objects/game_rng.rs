@@ -0,0 +1,36 @@
+use bevy_ecs::system::Resource;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+// The single source of randomness of the game world
+// All randomness (model variants, particles, ...) has to be drawn from this resource
+// so that the same level and seed always look and behave the same
+#[derive(Resource)]
+pub struct GameRng {
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,48 @@
+use bevy_ecs::system::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::level_loader::LevelMetadata;
+
+// The tunables of the game that hold for a whole level
+// A level can change them in its header, e.g. `gravity: -4` (see LevelMetadata)
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    // The speed the player runs with in cells per second
+    pub player_speed: f32,
+    // The charge the player loses per second in the glitch area
+    pub charge_depletion: f32,
+    // The charge a charge block gives the player
+    pub recharge_amount: f32,
+    // Seconds until a charge block can be used again
+    pub recharge_cooldown: f32,
+    // The acceleration along the z axis
+    pub gravity: f32,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            player_speed: 7.0,
+            charge_depletion: 1.0,
+            recharge_amount: 20.0,
+            recharge_cooldown: 10.0,
+            gravity: -9.81,
+        }
+    }
+}
+
+impl GameRules {
+    // Returns these rules with the values given in the level header
+    pub fn with_metadata(&self, metadata: &LevelMetadata) -> Self {
+        Self {
+            player_speed: metadata.player_speed.unwrap_or(self.player_speed),
+            charge_depletion: metadata.charge_depletion.unwrap_or(self.charge_depletion),
+            recharge_amount: metadata.recharge_amount.unwrap_or(self.recharge_amount),
+            recharge_cooldown: metadata
+                .recharge_cooldown
+                .unwrap_or(self.recharge_cooldown),
+            gravity: metadata.gravity.unwrap_or(self.gravity),
+        }
+    }
+}
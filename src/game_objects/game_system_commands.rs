@@ -4,7 +4,13 @@ use crate::{level_loader::ParsedLevel, object_types::Id};
 
 pub enum GameSystemCommand {
     LoadLevel(ParsedLevel),
-    SetCheckpoint(Id)
+    SetCheckpoint(Id),
+    // The player reached a goal with the given goal text
+    GoalReached(String),
+    // The level pack level with the given ID was finished
+    CompletePackLevel(String),
+    // Unlocks and loads the level pack level with the given ID
+    LoadPackLevel(String),
 }
 
 #[derive(Resource)]
@@ -26,4 +32,16 @@ impl GameSystemCommands {
     pub fn set_checkpoint(&mut self, id: Id) {
         self.commands.push(GameSystemCommand::SetCheckpoint(id));
     }
+
+    pub fn goal_reached(&mut self, goal_level_text: String) {
+        self.commands.push(GameSystemCommand::GoalReached(goal_level_text));
+    }
+
+    pub fn complete_pack_level(&mut self, id: String) {
+        self.commands.push(GameSystemCommand::CompletePackLevel(id));
+    }
+
+    pub fn load_pack_level(&mut self, id: String) {
+        self.commands.push(GameSystemCommand::LoadPackLevel(id));
+    }
 }
\ No newline at end of file
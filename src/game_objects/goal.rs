@@ -1,42 +1,99 @@
-use bevy_ecs::{component::Component, system::{Query, ResMut}, entity::Entity, query::With};
+use bevy_ecs::{component::Component, system::{Query, Res, ResMut, Resource}, entity::Entity, query::With};
+use rand::Rng;
 
+use crate::level_compressor;
 use crate::level_loader::{ParsedLevel, parse_level};
+use crate::object_types::Id;
 
-use super::{sensor::Sensor, player::Player, game_system_commands::GameSystemCommands};
+use super::{sensor::Sensor, player::Player, game_rng::GameRng, game_system_commands::GameSystemCommands};
 
 #[derive(Component)]
 pub struct Goal {
     pub goal_level_text: String,
+    // The level pack level the goal leads to, the next level of the pack if not set
+    pub level: Option<Id>,
+}
+
+// The level that is loaded when a goal is reached, taken from the `next` metadata of the current level
+// If it is not set, a level is generated from the goal text
+// Inside a level pack, the next level of the pack is loaded instead
+#[derive(Resource, Default)]
+pub struct NextLevel {
+    pub compressed_level: Option<String>,
+    pub pack_level: Option<PackLevelLinks>,
+}
+
+// Where the current level is in its level pack
+#[derive(Debug, Clone)]
+pub struct PackLevelLinks {
+    pub id: String,
+    // None for the last level of the pack
+    pub next_id: Option<String>,
+}
+
+impl NextLevel {
+    fn parse(&self) -> Option<ParsedLevel> {
+        let compressed_level = self.compressed_level.as_ref()?;
+        match level_compressor::decompress_level(compressed_level) {
+            Ok(level) => match parse_level(&level) {
+                Ok(parsed) => Some(parsed),
+                Err(e) => {
+                    log::warn!("Failed to parse the next level: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                log::warn!("Failed to decompress the next level: {}", e);
+                None
+            }
+        }
+    }
 }
 
 
 pub fn check_goal_reached_system(
     mut game_system_commands: ResMut<GameSystemCommands>,
+    mut rng: ResMut<GameRng>,
+    next_level: Res<NextLevel>,
     goal_sensor_query: Query<(&Goal, &Sensor)>,
     player_query: Query<Entity, With<Player>>,
 ) {
     // If a player has triggered a goal sensor, the level is finished
     let mut level_finished = false;
     let mut goal_level = String::new();
+    let mut goal_pack_level = None;
     'outer: for (goal, sensor) in goal_sensor_query.iter() {
         for triggered_by in &sensor.triggered_by {
             if player_query.get(*triggered_by).is_ok() {
                 level_finished = true;
                 goal_level = goal.goal_level_text.clone();
+                goal_pack_level = goal.level.as_ref().map(|id| id.id().to_string());
                 break 'outer;
             }
         }
     }
 
     if level_finished {
-        game_system_commands.load_level(parse_level(&string_to_level(&goal_level)).expect("Failed to parse level"));
+        game_system_commands.goal_reached(goal_level.clone());
+        if let Some(pack_level) = &next_level.pack_level {
+            game_system_commands.complete_pack_level(pack_level.id.clone());
+            if let Some(id) = goal_pack_level.or_else(|| pack_level.next_id.clone()) {
+                game_system_commands.load_pack_level(id);
+                return;
+            }
+        }
+        // Outside of a pack and after its last level, the `next` level or the goal text is loaded
+        let level = next_level.parse().unwrap_or_else(|| {
+            parse_level(&string_to_level(&goal_level, &mut *rng)).expect("Failed to parse level")
+        });
+        game_system_commands.load_level(level);
     }
 }
 
-fn string_to_level(input: &str) -> String {
+fn string_to_level(input: &str, rng: &mut impl Rng) -> String {
     let mut output = String::new();
     for line in input.lines() {
-        output.push_str(&line_to_level(line));
+        output.push_str(&line_to_level(line, rng));
         output.push_str("\n");
     }
     // add the player "+P" at the first cell (that is before the first whitespace following a character)
@@ -46,7 +103,7 @@ fn string_to_level(input: &str) -> String {
     output
 }
 
-fn line_to_level(input: &str) -> String {
+fn line_to_level(input: &str, rng: &mut impl Rng) -> String {
     let mut output_lines = vec![String::new(); 7];
 
     for block in input.chars() {
@@ -61,7 +118,7 @@ fn line_to_level(input: &str) -> String {
             // remove newline character and add
             let trimmed_line = line.trim();
             // with a one in 10 chance add a firework emitter
-            if rand::random::<f32>() < 0.1 {
+            if rng.gen::<f32>() < 0.1 {
                 output_lines[i+1].push_str(" N+N+F ");
             } else {
                 output_lines[i+1].push_str(" N+N ");
@@ -2,6 +2,6 @@ use bevy_ecs::system::Resource;
 
 #[derive(Resource)]
 pub struct Input {
-    pub player_movement: Option<cgmath::Vector3<f32>>, // if consumed  set to None
+    pub player_movement: Option<cgmath::Vector3<f32>>, // reset to None after every frame with a fixed tick
     pub player_paralized_cooldown: f32,
 }
\ No newline at end of file
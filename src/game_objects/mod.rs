@@ -14,4 +14,10 @@ pub mod game_system_commands;
 pub mod checkpoint;
 pub mod model_manager;
 pub mod dust;
-pub mod firework;
\ No newline at end of file
+pub mod firework;
+pub mod game_rng;
+pub mod game_rules;
+pub mod platform;
+pub mod teleporter;
+pub mod trigger;
+pub mod block_id;
\ No newline at end of file
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use bevy_ecs::system::Resource;
-use rand::seq::IteratorRandom;
+use rand::{seq::IteratorRandom, Rng};
 
 use crate::{model::ModelHandle, object_types::BlockType};
 
@@ -15,13 +15,24 @@ impl ModelManager {
         Self { handle_store }
     }
 
-    pub fn get_handle(&self, block_type: &BlockType) -> Option<ModelHandle> {
+    // Creates a model manager without any loaded models (e.g. for running without a GPU)
+    // Every block type maps to the same placeholder handle which is never rendered
+    pub fn headless() -> Self {
+        let placeholder = ModelHandle::from(0);
+        let handle_store = BlockType::iter()
+            .map(|block_type| (block_type, vec![placeholder]))
+            .collect();
+        Self { handle_store }
+    }
+
+    // Returns one of the model variants of the block type chosen by the given rng
+    pub fn get_handle(&self, block_type: &BlockType, rng: &mut impl Rng) -> Option<ModelHandle> {
         self.handle_store
             .get(block_type)
             .and_then(|handles|
                 handles
                     .iter()
-                    .choose(&mut rand::thread_rng())
+                    .choose(rng)
                     .cloned()
             )
     }
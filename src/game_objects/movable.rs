@@ -12,7 +12,8 @@ use rand::Rng;
 use crate::{object_types::BlockType, physics::PhysicsSystem};
 
 use super::{
-    dust::DustParticle, model_manager::ModelManager, physics_body::PhysicsBody, player::Player,
+    dust::DustParticle, game_rng::GameRng, model_manager::ModelManager, physics_body::PhysicsBody,
+    player::Player,
     position::Position, renderable::Renderable, time_keeper::TimeKeeper,
 };
 
@@ -102,6 +103,7 @@ pub fn spawn_dust_on_moving_objects_system(
     physics_system: Res<PhysicsSystem>,
     query: Query<(&Position, &PhysicsBody), With<Movable>>,
     model_manager: Res<ModelManager>,
+    mut rng: ResMut<GameRng>,
 ) {
     // Only move the player if we are in a physics tick
     // Otherwise the player will be frame rate dependent
@@ -110,7 +112,7 @@ pub fn spawn_dust_on_moving_objects_system(
     }
 
     // randomly only spawn every 3th tick
-    if rand::thread_rng().gen_range(0..3) != 0 {
+    if rng.gen_range(0..3) != 0 {
         return;
     }
 
@@ -119,7 +121,6 @@ pub fn spawn_dust_on_moving_objects_system(
         let player_velocity_magnitude = physics_system.get_velocity_magnitude(physics_body.body);
 
         if player_velocity_magnitude > 2.0 {
-            let mut rng = rand::thread_rng();
             let player_position = position.position;
             let range = 0.25;
             let random_point =
@@ -152,7 +153,7 @@ pub fn spawn_dust_on_moving_objects_system(
                 DustParticle::new(random_velocity, random_color, 0.1, 1.5),
                 pos,
                 Renderable {
-                    mesh: model_manager.get_handle(&BlockType::Cube).unwrap(),
+                    mesh: model_manager.get_handle(&BlockType::Cube, &mut *rng).unwrap(),
                 },
             ));
         }
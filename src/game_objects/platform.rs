@@ -0,0 +1,137 @@
+use bevy_ecs::{
+    component::Component,
+    query::{Or, With},
+    system::{Query, Res, ResMut},
+};
+use cgmath::{InnerSpace, Vector3};
+
+use crate::{
+    object_types::{Axis, DoorCondition, PlatformPath},
+    physics::PhysicsSystem,
+};
+
+use super::{
+    constants::TICKS_PER_SECOND,
+    movable::Movable,
+    physics_body::PhysicsBody,
+    player::Player,
+    sensor::Sensor,
+    time_keeper::TimeKeeper,
+    trigger::{active_trigger_ids, Trigger},
+};
+
+// Platforms carry the player and everything that can be moved
+type Riders = Or<(With<Player>, With<Movable>)>;
+
+#[derive(Component)]
+pub struct Platform {
+    start: Vector3<f32>,
+    // The offsets of the waypoints from the start, the first waypoint is the start itself
+    waypoints: Vec<Vector3<f32>>,
+    condition: Option<DoorCondition>,
+    // Cells per second
+    speed: f32,
+    // 0 at the start and 1 at the end of the path
+    pub progress: f32,
+    // The current direction of platforms without condition
+    pub moving_to_end: bool,
+}
+
+impl Platform {
+    pub fn new(start: Vector3<f32>, path: &PlatformPath) -> Self {
+        let mut waypoint = Vector3::new(0.0, 0.0, 0.0);
+        let mut waypoints = vec![waypoint];
+        for (axis, distance) in &path.segments {
+            let distance = *distance as f32;
+            // Rows of the level go into the negative y direction of the world
+            waypoint += match axis {
+                Axis::X => Vector3::new(distance, 0.0, 0.0),
+                Axis::Y => Vector3::new(0.0, -distance, 0.0),
+                Axis::Z => Vector3::new(0.0, 0.0, distance),
+            };
+            waypoints.push(waypoint);
+        }
+        Self {
+            start,
+            waypoints,
+            condition: path.condition.clone(),
+            speed: path.speed as f32,
+            progress: 0.0,
+            moving_to_end: true,
+        }
+    }
+
+    fn path_length(&self) -> f32 {
+        self.waypoints
+            .windows(2)
+            .map(|segment| (segment[1] - segment[0]).magnitude())
+            .sum()
+    }
+
+    /// The position on the path the platform moves to in the next physics step
+    pub fn position(&self) -> Vector3<f32> {
+        let mut distance_left = self.progress * self.path_length();
+        for segment in self.waypoints.windows(2) {
+            let offset = segment[1] - segment[0];
+            let length = offset.magnitude();
+            if distance_left < length {
+                return self.start + segment[0] + offset * (distance_left / length);
+            }
+            distance_left -= length;
+        }
+        self.start + self.waypoints[self.waypoints.len() - 1]
+    }
+}
+
+pub fn move_platform_system(
+    time_keeper: Res<TimeKeeper>,
+    mut physics_system: ResMut<PhysicsSystem>,
+    mut platform_query: Query<(&mut Platform, &PhysicsBody, &Sensor)>,
+    trigger_query: Query<(&Sensor, &Trigger)>,
+    rider_query: Query<&PhysicsBody, Riders>,
+) {
+    // Only move platforms if we are in a physics tick
+    // Otherwise the movement will be frame rate dependent
+    if !time_keeper.is_in_fixed_tick() {
+        return;
+    }
+
+    let active_trigger_ids = active_trigger_ids(&trigger_query);
+    for (mut platform, body, sensor) in &mut platform_query {
+        let path_length = platform.path_length();
+        if path_length == 0.0 {
+            continue;
+        }
+
+        let moving_to_end = match &platform.condition {
+            Some(condition) => condition.is_satisfied(&|id| active_trigger_ids.contains(id)),
+            None => {
+                if platform.progress >= 1.0 {
+                    platform.moving_to_end = false;
+                } else if platform.progress <= 0.0 {
+                    platform.moving_to_end = true;
+                }
+                platform.moving_to_end
+            }
+        };
+
+        let step = platform.speed / TICKS_PER_SECOND as f32 / path_length;
+        let position_before = platform.position();
+        platform.progress = if moving_to_end {
+            (platform.progress + step).min(1.0)
+        } else {
+            (platform.progress - step).max(0.0)
+        };
+        let offset = platform.position() - position_before;
+        physics_system.set_kinematic_target(body.body, platform.position());
+
+        // Riders are carried along horizontally
+        // Vertically they are pushed up by the platform or follow it down with gravity
+        for rider in &sensor.triggered_by {
+            if let Ok(rider_body) = rider_query.get(*rider) {
+                physics_system
+                    .translate_body(rider_body.body, Vector3::new(offset.x, offset.y, 0.0));
+            }
+        }
+    }
+}
@@ -14,8 +14,9 @@ use crate::{
 };
 
 use super::{
-    constants::TICKS_PER_SECOND, dust::DustParticle, input::Input, model_manager::ModelManager,
-    physics_body::PhysicsBody, position::Position, renderable::Renderable, time_keeper::TimeKeeper,
+    constants::TICKS_PER_SECOND, dust::DustParticle, game_rng::GameRng, game_rules::GameRules,
+    input::Input, model_manager::ModelManager, physics_body::PhysicsBody, position::Position,
+    renderable::Renderable, time_keeper::TimeKeeper,
 };
 
 #[derive(Component)]
@@ -33,6 +34,7 @@ pub fn move_player_system(
     mut input: ResMut<Input>,
     camera: Res<StereoCamera>,
     time_keeper: Res<TimeKeeper>,
+    game_rules: Res<GameRules>,
     mut query: Query<(&mut Position, &PhysicsBody), With<Player>>,
 ) {
     // Only move the player if we are in a physics tick
@@ -46,9 +48,9 @@ pub fn move_player_system(
         return;
     }
 
+    // The movement is reset by the game world after all fixed ticks of a frame have run
     let requested_movement = input
         .player_movement
-        .take()
         .unwrap_or(cgmath::Vector3::new(0.0, 0.0, 0.0));
     let camera_look_direction = camera.get_camera_view_direction_projected_to_ground();
 
@@ -66,7 +68,7 @@ pub fn move_player_system(
     if direction.magnitude() > 1.0 {
         direction = direction.normalize();
     }
-    let player_max_speed = 7.0;
+    let player_max_speed = game_rules.player_speed;
     let direction = camera_look_direction_rotation_matrix * direction * player_max_speed;
 
     for (mut position, physics_body) in &mut query {
@@ -98,6 +100,7 @@ pub fn spawn_dust_on_move_player_system(
     physics_system: Res<PhysicsSystem>,
     query: Query<(&Position, &PhysicsBody), With<Player>>,
     model_manager: Res<ModelManager>,
+    mut rng: ResMut<GameRng>,
 ) {
     // Only move the player if we are in a physics tick
     // Otherwise the player will be frame rate dependent
@@ -106,7 +109,7 @@ pub fn spawn_dust_on_move_player_system(
     }
 
     // randomly only spawn every 3th tick
-    if rand::thread_rng().gen_range(0..3) != 0 {
+    if rng.gen_range(0..3) != 0 {
         return;
     }
 
@@ -115,7 +118,6 @@ pub fn spawn_dust_on_move_player_system(
         let player_velocity_magnitude = physics_system.get_velocity_magnitude(physics_body.body);
 
         if player_velocity_magnitude > 2.0 {
-            let mut rng = rand::thread_rng();
             let player_position = position.position;
             let range = 0.25;
             let random_point =
@@ -146,7 +148,9 @@ pub fn spawn_dust_on_move_player_system(
                 DustParticle::new(random_velocity, random_color, random_size, lifetime),
                 pos,
                 Renderable {
-                    mesh: model_manager.get_handle(&BlockType::Cube).unwrap(),
+                    mesh: model_manager
+                        .get_handle(&BlockType::Cube, &mut *rng)
+                        .unwrap(),
                 },
             ));
 
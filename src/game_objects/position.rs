@@ -2,7 +2,7 @@ use bevy_ecs::component::Component;
 use cgmath::Rotation3;
 
 
-#[derive(Component, Clone, Copy, Debug)]
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
 pub struct Position {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
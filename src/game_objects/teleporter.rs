@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{Or, With},
+    system::{Commands, Query, Res, ResMut},
+};
+use cgmath::{Vector3, Vector4};
+use rand::Rng;
+
+use crate::{
+    object_types::{BlockType, Id},
+    physics::PhysicsSystem,
+};
+
+use super::{
+    constants::TICKS_PER_SECOND, dust::DustParticle, game_rng::GameRng,
+    model_manager::ModelManager, movable::Movable, physics_body::PhysicsBody, player::Player,
+    position::Position, renderable::Renderable, sensor::Sensor, time_keeper::TimeKeeper,
+};
+
+// Ticks an entity can not teleport again after it arrived, only counted while it is off all teleporters
+const TELEPORT_COOLDOWN_TICKS: u32 = TICKS_PER_SECOND / 2;
+
+// The player and the objects it pulls can be teleported
+type Travelers = Or<(With<Player>, With<Movable>)>;
+type TravelerState<'a> = (
+    &'a PhysicsBody,
+    &'a Position,
+    Option<&'a Player>,
+    Option<&'a TeleportCooldown>,
+);
+
+#[derive(Component)]
+pub struct Teleporter {
+    pair_id: Id,
+    // Lights up when something is teleported and fades out afterwards
+    glow: f32,
+}
+
+impl Teleporter {
+    pub fn new(pair_id: Id) -> Self {
+        Self { pair_id, glow: 0.0 }
+    }
+}
+
+// Prevents a teleported entity from bouncing back from the partner teleporter it arrived on
+#[derive(Component)]
+pub struct TeleportCooldown {
+    pub remaining_ticks: u32,
+}
+
+pub fn teleport_cooldown_system(
+    mut commands: Commands,
+    time_keeper: Res<TimeKeeper>,
+    teleporter_query: Query<&Sensor, With<Teleporter>>,
+    mut cooldown_query: Query<(Entity, &mut TeleportCooldown)>,
+) {
+    // The cooldown counts fixed ticks
+    if !time_keeper.is_in_fixed_tick() {
+        return;
+    }
+
+    let on_teleporter = teleporter_query
+        .iter()
+        .flat_map(|sensor| sensor.triggered_by.iter().copied())
+        .collect::<HashSet<_>>();
+    for (entity, mut cooldown) in &mut cooldown_query {
+        if on_teleporter.contains(&entity) {
+            continue;
+        }
+        cooldown.remaining_ticks = cooldown.remaining_ticks.saturating_sub(1);
+        if cooldown.remaining_ticks == 0 {
+            commands.entity(entity).remove::<TeleportCooldown>();
+        }
+    }
+}
+
+pub fn teleport_system(
+    mut commands: Commands,
+    time_keeper: Res<TimeKeeper>,
+    mut physics_system: ResMut<PhysicsSystem>,
+    model_manager: Res<ModelManager>,
+    mut rng: ResMut<GameRng>,
+    mut teleporter_query: Query<(Entity, &mut Teleporter, &Sensor, &Position)>,
+    traveler_query: Query<TravelerState, Travelers>,
+) {
+    // The sensors only change in fixed ticks
+    if !time_keeper.is_in_fixed_tick() {
+        return;
+    }
+
+    // Movables only travel while the player pulls them
+    let pulled_objects = traveler_query
+        .iter()
+        .filter_map(|(_body, _position, player, _cooldown)| player)
+        .flat_map(|player| player.pulled_objects.iter().copied())
+        .collect::<HashSet<_>>();
+    let teleporters = teleporter_query
+        .iter()
+        .map(|(entity, teleporter, sensor, position)| {
+            (
+                entity,
+                teleporter.pair_id.clone(),
+                sensor.triggered_by.clone(),
+                position.position,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut teleported = HashSet::new();
+    let mut used_teleporters = Vec::new();
+    for (entity, pair_id, triggered_by, position) in &teleporters {
+        let Some((partner, _, _, partner_position)) = teleporters
+            .iter()
+            .find(|(other, other_pair_id, _, _)| other != entity && other_pair_id == pair_id)
+        else {
+            continue;
+        };
+
+        for traveler in triggered_by {
+            let Ok((body, traveler_position, player, cooldown)) = traveler_query.get(*traveler)
+            else {
+                continue;
+            };
+            if teleported.contains(traveler) || cooldown.is_some() {
+                continue;
+            }
+            if player.is_none() && !pulled_objects.contains(traveler) {
+                continue;
+            }
+
+            // Keep the offset to the teleporter so that the traveler arrives at the same height
+            let arrival = partner_position + (traveler_position.position - position);
+            physics_system.set_translation(body.body, arrival);
+            commands.entity(*traveler).insert(TeleportCooldown {
+                remaining_ticks: TELEPORT_COOLDOWN_TICKS,
+            });
+            teleported.insert(*traveler);
+            used_teleporters.extend([*entity, *partner]);
+
+            spawn_teleport_particles(
+                &mut commands,
+                &model_manager,
+                &mut rng,
+                traveler_position.position,
+            );
+            spawn_teleport_particles(&mut commands, &model_manager, &mut rng, arrival);
+            log::info!("Teleport {:?} via #{}", traveler, pair_id.id());
+        }
+    }
+
+    for (entity, mut teleporter, _sensor, _position) in &mut teleporter_query {
+        if used_teleporters.contains(&entity) {
+            teleporter.glow = 1.0;
+        } else {
+            teleporter.glow = (teleporter.glow - 2.0 / TICKS_PER_SECOND as f32).max(0.0);
+        }
+    }
+}
+
+// A ring of particles rising around the traveler
+// The particles are placed in the world so that they have the right depth in both eyes
+fn spawn_teleport_particles(
+    commands: &mut Commands,
+    model_manager: &ModelManager,
+    rng: &mut GameRng,
+    center: Vector3<f32>,
+) {
+    let particle_count = 12;
+    for i in 0..particle_count {
+        let angle = i as f32 / particle_count as f32 * std::f32::consts::TAU;
+        let direction = Vector3::new(angle.cos(), angle.sin(), 0.0);
+        let velocity = direction * 0.6 + Vector3::new(0.0, 0.0, rng.gen_range(0.8..1.2));
+        let color = Vector3::new(rng.gen_range(0.3..0.5), rng.gen_range(0.8..1.0), 1.0);
+        let size = rng.gen_range(0.06..0.1);
+
+        let position = Position {
+            position: center + direction * 0.4 - Vector3::new(0.0, 0.0, 0.4),
+            scale: Vector3::new(size, size, size),
+            color: Vector4::new(color.x, color.y, color.z, 1.0),
+            ..Default::default()
+        };
+
+        commands.spawn((
+            DustParticle::new(velocity, color, size, 0.8),
+            position,
+            Renderable {
+                mesh: model_manager
+                    .get_handle(&BlockType::Cube, &mut *rng)
+                    .unwrap(),
+            },
+        ));
+    }
+}
+
+// Tints the teleporter model and lets it light up after it was used
+pub fn animate_teleporter_system(mut query: Query<(&Teleporter, &mut Position)>) {
+    for (teleporter, mut position) in &mut query {
+        let brightness = 1.0 + teleporter.glow;
+        position.color = Vector4::new(0.4 * brightness, 0.9 * brightness, 1.0 * brightness, 1.0);
+    }
+}
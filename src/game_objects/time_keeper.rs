@@ -1,57 +1,222 @@
 use bevy_ecs::system::Resource;
 
+use super::constants::MAX_FIXED_TICKS_PER_FRAME;
+
+// Rounding errors smaller than this are ignored when counting due fixed ticks
+// so that advancing a clock by exactly one tick always yields exactly one tick
+const EPSILON_MS: f64 = 1e-6;
+
+// The clock the time keeper reads the current time from
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimeSource {
+    // The wall clock
+    Real,
+    // A clock that only advances via TimeKeeper::advance_manual_clock() (e.g. in headless mode or tests)
+    Manual {
+        now_ms: f64,
+    },
+    // The wall clock running slower or faster by the given factor
+    Scaled {
+        scale: f64,
+        real_origin_ms: f64,
+        origin_ms: f64,
+    },
+}
+
+impl TimeSource {
+    pub fn manual() -> Self {
+        TimeSource::Manual { now_ms: 0.0 }
+    }
+
+    pub fn scaled(scale: f64) -> Self {
+        let now = instant::now();
+        TimeSource::Scaled {
+            scale,
+            real_origin_ms: now,
+            origin_ms: now,
+        }
+    }
+
+    fn now_ms(&self) -> f64 {
+        match self {
+            TimeSource::Real => instant::now(),
+            TimeSource::Manual { now_ms } => *now_ms,
+            TimeSource::Scaled {
+                scale,
+                real_origin_ms,
+                origin_ms,
+            } => origin_ms + (instant::now() - real_origin_ms) * scale,
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct TimeKeeper {
     ticks_per_second: u32,
-    last_fixed_tick_ms: f64,
-    last_variable_tick_ms: f64,
+    time_source: TimeSource,
+
+    // The time of the last call to begin_frame()
+    last_frame_ms: f64,
+    // Time that has passed but was not yet simulated in fixed ticks
+    accumulator_ms: f64,
+    // The (simulated) time of the last schedule run
+    last_run_ms: f64,
     variable_delta_ms: f64,
     is_in_fixed_tick: bool,
+    // The number of fixed ticks simulated so far
+    tick_count: u64,
 }
 
 impl TimeKeeper {
     pub fn new(ticks_per_second: u32) -> Self {
+        Self::with_time_source(ticks_per_second, TimeSource::Real)
+    }
+
+    pub fn with_time_source(ticks_per_second: u32, time_source: TimeSource) -> Self {
+        let now = time_source.now_ms();
         Self {
             ticks_per_second,
-            last_fixed_tick_ms: instant::now(),
-            last_variable_tick_ms: instant::now(),
+            time_source,
+            last_frame_ms: now,
+            accumulator_ms: 0.0,
+            last_run_ms: now,
             variable_delta_ms: 0.0,
             is_in_fixed_tick: false,
+            tick_count: 0,
         }
     }
 
-    // Returns true if a tick has passed since the last call to tick()
-    // and updates the last_fixed_tick_ms
-    pub fn tick(&mut self) -> bool {
-        let now = instant::now();
-        self.variable_delta_ms = now - self.last_variable_tick_ms;
-        self.last_variable_tick_ms = now;
-        let fixed_delta = now - self.last_fixed_tick_ms;
-        let ms_per_tick = 1000.0 / self.ticks_per_second as f64;
-        if fixed_delta >= ms_per_tick {
-            self.last_fixed_tick_ms = now;
-            self.is_in_fixed_tick = true;
-            true
-        } else {
-            self.is_in_fixed_tick = false;
-            false
+    // Replaces the time source
+    // Time that passed before the switch but was not yet simulated is kept
+    pub fn set_time_source(&mut self, time_source: TimeSource) {
+        let now = time_source.now_ms();
+        self.time_source = time_source;
+        self.last_frame_ms = now;
+        self.last_run_ms = now - self.accumulator_ms;
+    }
+
+    // Advances the manual clock by the given amount of milliseconds
+    // Does nothing if the time keeper does not use a manual clock
+    pub fn advance_manual_clock(&mut self, delta_ms: f64) {
+        if let TimeSource::Manual { now_ms } = &mut self.time_source {
+            *now_ms += delta_ms;
+        }
+    }
+
+    // Returns the duration of one fixed tick in milliseconds
+    pub fn ms_per_tick(&self) -> f64 {
+        1000.0 / self.ticks_per_second as f64
+    }
+
+    // Reads the clock and returns how many fixed ticks have to be simulated in this frame.
+    // At most MAX_FIXED_TICKS_PER_FRAME ticks are returned, the rest of the elapsed time is dropped.
+    pub fn begin_frame(&mut self) -> u32 {
+        let now = self.time_source.now_ms();
+        // the clock might go backwards when the time source is changed
+        self.accumulator_ms += (now - self.last_frame_ms).max(0.0);
+        self.last_frame_ms = now;
+
+        let ms_per_tick = self.ms_per_tick();
+        let due_ticks = ((self.accumulator_ms + EPSILON_MS) / ms_per_tick).floor() as u32;
+        if due_ticks > MAX_FIXED_TICKS_PER_FRAME {
+            log::warn!(
+                "Simulation is falling behind, dropping {} ticks",
+                due_ticks - MAX_FIXED_TICKS_PER_FRAME
+            );
+            self.accumulator_ms = MAX_FIXED_TICKS_PER_FRAME as f64 * ms_per_tick;
+            self.last_run_ms = now - self.accumulator_ms;
+            return MAX_FIXED_TICKS_PER_FRAME;
         }
+        due_ticks
+    }
+
+    // Marks the next schedule run as a fixed tick
+    // Must only be called as often as begin_frame() returned
+    pub fn begin_fixed_tick(&mut self) {
+        self.accumulator_ms = (self.accumulator_ms - self.ms_per_tick()).max(0.0);
+        let run_ms = self.last_frame_ms - self.accumulator_ms;
+        self.variable_delta_ms = run_ms - self.last_run_ms;
+        self.last_run_ms = run_ms;
+        self.is_in_fixed_tick = true;
+        self.tick_count += 1;
     }
 
-    // Returns true if the last call to tick() returned true
+    // Marks the next schedule run as a frame without a fixed tick
+    pub fn begin_variable_frame(&mut self) {
+        self.variable_delta_ms = (self.last_frame_ms - self.last_run_ms).max(0.0);
+        self.last_run_ms = self.last_frame_ms;
+        self.is_in_fixed_tick = false;
+    }
+
+    // Returns true if the current schedule run is a fixed tick
     // This means that in this system update cycle all fixed updates
     // should be performed
     pub fn is_in_fixed_tick(&self) -> bool {
         self.is_in_fixed_tick
     }
 
+    // Returns the number of fixed ticks simulated so far
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
     // Returns the current time in seconds
     pub fn now() -> f64 {
         instant::now() / 1000.0
     }
 
-    // Returns the time since the last call to tick() in seconds
+    // Returns the (simulated) time since the last schedule run in seconds
     pub fn delta_seconds(&self) -> f32 {
         self.variable_delta_ms as f32 / 1000.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_frame(time_keeper: &mut TimeKeeper) -> u32 {
+        let ticks = time_keeper.begin_frame();
+        for _ in 0..ticks {
+            time_keeper.begin_fixed_tick();
+        }
+        ticks
+    }
+
+    #[test]
+    fn test_tick_count_is_independent_of_frame_rate() {
+        for frames_per_second in [20, 30, 60, 144] {
+            let mut time_keeper = TimeKeeper::with_time_source(60, TimeSource::manual());
+            for _ in 0..frames_per_second * 2 {
+                time_keeper.advance_manual_clock(1000.0 / frames_per_second as f64);
+                run_frame(&mut time_keeper);
+            }
+            assert_eq!(
+                time_keeper.tick_count(),
+                120,
+                "at {} fps",
+                frames_per_second
+            );
+        }
+    }
+
+    #[test]
+    fn test_ticks_per_frame_are_capped() {
+        let mut time_keeper = TimeKeeper::with_time_source(60, TimeSource::manual());
+        time_keeper.advance_manual_clock(10_000.0);
+        assert_eq!(run_frame(&mut time_keeper), MAX_FIXED_TICKS_PER_FRAME);
+        // the dropped time is not simulated later on
+        assert_eq!(run_frame(&mut time_keeper), 0);
+    }
+
+    #[test]
+    fn test_delta_seconds_of_fixed_ticks() {
+        let mut time_keeper = TimeKeeper::with_time_source(60, TimeSource::manual());
+        time_keeper.advance_manual_clock(1000.0 / 30.0);
+        assert_eq!(time_keeper.begin_frame(), 2);
+        time_keeper.begin_fixed_tick();
+        assert!((time_keeper.delta_seconds() - 1.0 / 60.0).abs() < 1e-6);
+        time_keeper.begin_fixed_tick();
+        assert!((time_keeper.delta_seconds() - 1.0 / 60.0).abs() < 1e-6);
+    }
+}
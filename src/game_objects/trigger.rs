@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+use bevy_ecs::{
+    component::Component,
+    system::{Query, Res},
+};
+use cgmath::Vector4;
+
+use crate::object_types::{Id, TriggerMode};
+
+use super::{
+    constants::TICKS_PER_SECOND, position::Position, sensor::Sensor, time_keeper::TimeKeeper,
+};
+
+// The state of a trigger block, doors open according to is_active() and not the raw sensor state
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Trigger {
+    mode: TriggerMode,
+    active: bool,
+    // Whether the sensor was pressed in the previous tick, used to detect new presses
+    was_pressed: bool,
+    // The ticks a timed trigger stays active after it was released
+    remaining_ticks: u32,
+}
+
+impl Trigger {
+    pub fn new(mode: TriggerMode) -> Self {
+        Self {
+            mode,
+            active: false,
+            was_pressed: false,
+            remaining_ticks: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn update(&mut self, pressed: bool) {
+        let just_pressed = pressed && !self.was_pressed;
+        self.was_pressed = pressed;
+
+        match self.mode {
+            TriggerMode::Momentary => self.active = pressed,
+            TriggerMode::Latching => self.active |= pressed,
+            TriggerMode::Toggle => {
+                if just_pressed {
+                    self.active = !self.active;
+                }
+            }
+            TriggerMode::Timed(seconds) => {
+                if pressed {
+                    self.remaining_ticks = seconds * TICKS_PER_SECOND;
+                } else {
+                    self.remaining_ticks = self.remaining_ticks.saturating_sub(1);
+                }
+                self.active = pressed || self.remaining_ticks > 0;
+            }
+        }
+    }
+
+    // How much the trigger lights up, timed triggers fade out until they turn off
+    fn glow(&self) -> f32 {
+        match self.mode {
+            TriggerMode::Timed(seconds) if self.active && !self.was_pressed => {
+                self.remaining_ticks as f32 / (seconds * TICKS_PER_SECOND) as f32
+            }
+            _ if self.active => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+// Returns the IDs of all active triggers, the conditions of doors and platforms are evaluated on these
+pub fn active_trigger_ids(trigger_query: &Query<(&Sensor, &Trigger)>) -> HashSet<Id> {
+    trigger_query
+        .iter()
+        .filter(|(_sensor, trigger)| trigger.is_active())
+        .filter_map(|(sensor, _trigger)| sensor.id.clone())
+        .collect()
+}
+
+pub fn update_trigger_system(
+    time_keeper: Res<TimeKeeper>,
+    mut query: Query<(&Sensor, &mut Trigger)>,
+) {
+    // The sensors only change in fixed ticks, timed triggers count fixed ticks
+    if !time_keeper.is_in_fixed_tick() {
+        return;
+    }
+
+    for (sensor, mut trigger) in &mut query {
+        trigger.update(sensor.triggered);
+    }
+}
+
+// Tints the trigger model by its mode and lets it light up while it is active
+pub fn animate_trigger_system(mut query: Query<(&Trigger, &mut Position)>) {
+    for (trigger, mut position) in &mut query {
+        let tint = match trigger.mode {
+            TriggerMode::Momentary => Vector4::new(1.0, 1.0, 1.0, 1.0),
+            TriggerMode::Latching => Vector4::new(0.5, 0.7, 1.0, 1.0),
+            TriggerMode::Toggle => Vector4::new(0.8, 0.5, 1.0, 1.0),
+            TriggerMode::Timed(_) => Vector4::new(1.0, 0.7, 0.4, 1.0),
+        };
+        let brightness = 1.0 + trigger.glow();
+        position.color = Vector4::new(
+            tint.x * brightness,
+            tint.y * brightness,
+            tint.z * brightness,
+            1.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigger_modes() {
+        let presses = [true, false, true, true, false];
+        let states = |mode| {
+            let mut trigger = Trigger::new(mode);
+            presses
+                .iter()
+                .map(|&pressed| {
+                    trigger.update(pressed);
+                    trigger.is_active()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            states(TriggerMode::Momentary),
+            vec![true, false, true, true, false]
+        );
+        assert_eq!(
+            states(TriggerMode::Latching),
+            vec![true, true, true, true, true]
+        );
+        assert_eq!(
+            states(TriggerMode::Toggle),
+            vec![true, true, false, false, false]
+        );
+
+        let mut trigger = Trigger::new(TriggerMode::Timed(1));
+        trigger.update(true);
+        for _ in 0..TICKS_PER_SECOND - 1 {
+            trigger.update(false);
+            assert!(trigger.is_active());
+        }
+        trigger.update(false);
+        assert!(!trigger.is_active());
+    }
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::level_pack::LevelPackProgress;
+
+// What the host page needs to draw a HUD (charge meter, timer, ...)
+// GameWorld takes a new snapshot after every frame (see GameWorld::game_status)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameStatus {
+    // Whether a level is loaded, all other fields are meaningless without one
+    pub level_loaded: bool,
+    pub charge: f32,
+    pub max_charge: f32,
+    // Whether the player stands on a glitch cell and loses charge
+    pub in_glitch_area: bool,
+    // The ID of the checkpoint the player respawns at, None if no checkpoint was reached yet
+    pub checkpoint: Option<String>,
+    // Seconds since the level was loaded, dying does not reset the timer
+    pub elapsed_time: f32,
+    // The time in seconds a good run should take, from the level header
+    pub par_time: Option<f32>,
+    // The ID of the level pack level that is played, None if the level is not from a pack
+    pub pack_level: Option<String>,
+    // The unlocked and completed levels, None if no level pack is loaded
+    pub level_pack_progress: Option<LevelPackProgress>,
+    // Whether the level is played backwards
+    pub rewinding: bool,
+    // The number of box pushes that can be undone
+    pub box_undo_steps: usize,
+}
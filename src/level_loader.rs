@@ -1,20 +1,24 @@
 use std::collections::HashSet;
 use std::fmt::Debug;
+use std::ops::Range;
 
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_while_m_n};
-use nom::character::complete::{newline, space1};
+use nom::bytes::complete::{tag, take_while1, take_while_m_n};
 
-use nom::combinator::{opt, value};
-use nom::multi::{separated_list0, separated_list1};
+use nom::combinator::{map, opt, value};
+use nom::multi::{many1, separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded, separated_pair};
 
-use nom::IResult;
+use nom::{IResult, Offset};
 
 use serde::{Deserialize, Serialize};
 
-use crate::object_types::{Block, BoxType, Id, LinearEnemyDirection};
+use crate::object_types::{
+    Axis, Block, BoxType, DoorCondition, EnemyParams, Id, LinearEnemyDirection, PatrolMode,
+    PatrolPath, PlatformPath, PropertyType, PropertyValue, TriggerMode,
+};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cell {
     is_glitch_area: bool,
     block_stack: Vec<(Block, Option<Id>)>,
@@ -25,6 +29,20 @@ impl Cell {
         self.block_stack.iter()
     }
 
+    /// Iterates over the blocks of the cell at the given position together with their IDs.
+    /// Blocks without an ID in the level text get a generated one (see Id::generated).
+    pub fn blocks_with_ids(&self, (x, y): (i32, i32)) -> impl Iterator<Item = (&Block, Id)> {
+        self.block_stack
+            .iter()
+            .enumerate()
+            .map(move |(index, (block, id))| {
+                (
+                    block,
+                    id.clone().unwrap_or_else(|| Id::generated(x, y, index)),
+                )
+            })
+    }
+
     pub fn block_stack_iter_mut(&mut self) -> impl Iterator<Item = &mut (Block, Option<Id>)> {
         self.block_stack.iter_mut()
     }
@@ -34,16 +52,132 @@ impl Cell {
     }
 }
 
-#[derive(PartialEq)]
+// An error found while validating a level, located at the cell (x, y) if it belongs to one
+#[derive(Debug)]
+pub struct CellError {
+    pub message: String,
+    pub cell: Option<(i32, i32)>,
+}
+
+impl CellError {
+    fn new(message: &str, cell: Option<(i32, i32)>) -> Self {
+        Self {
+            message: message.to_string(),
+            cell,
+        }
+    }
+}
+
+/// The optional header of a level, given as `key: value` lines before the grid
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    // The time in seconds a good run should take
+    pub par_time: Option<f32>,
+    // The compressed level (as used in level URLs) that is loaded when a goal is reached
+    pub next: Option<String>,
+    pub eye_distance: Option<f32>,
+    pub description: Option<String>,
+    // Overrides of the game rules for this level (see GameRules)
+    pub player_speed: Option<f32>,
+    pub charge_depletion: Option<f32>,
+    pub recharge_amount: Option<f32>,
+    pub recharge_cooldown: Option<f32>,
+    pub gravity: Option<f32>,
+}
+
+impl LevelMetadata {
+    const KEYS: [&'static str; 11] = [
+        "title",
+        "author",
+        "par_time",
+        "next",
+        "eye_distance",
+        "description",
+        "player_speed",
+        "charge_depletion",
+        "recharge_amount",
+        "recharge_cooldown",
+        "gravity",
+    ];
+
+    // Returns the keys and values of all set fields in the order of KEYS
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        let values = [
+            self.title.clone(),
+            self.author.clone(),
+            self.par_time.map(|par_time| par_time.to_string()),
+            self.next.clone(),
+            self.eye_distance
+                .map(|eye_distance| eye_distance.to_string()),
+            self.description.clone(),
+            self.player_speed
+                .map(|player_speed| player_speed.to_string()),
+            self.charge_depletion
+                .map(|charge_depletion| charge_depletion.to_string()),
+            self.recharge_amount
+                .map(|recharge_amount| recharge_amount.to_string()),
+            self.recharge_cooldown
+                .map(|recharge_cooldown| recharge_cooldown.to_string()),
+            self.gravity.map(|gravity| gravity.to_string()),
+        ];
+        Self::KEYS
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| value.map(|value| (key, value)))
+            .collect()
+    }
+
+    // Sets the value of the given key, on failure the expected tokens are returned
+    fn set(&mut self, key: &str, value: &str) -> Result<(), (SpanPart, Vec<&'static str>)> {
+        let parse_number = |value: &str| {
+            value
+                .parse::<f32>()
+                .map_err(|_| (SpanPart::Value, vec!["<number>"]))
+        };
+        let parse_non_negative_number = |value: &str| match value.parse::<f32>() {
+            Ok(number) if number.is_finite() && number >= 0.0 => Ok(number),
+            _ => Err((SpanPart::Value, vec!["<non-negative number>"])),
+        };
+        match key {
+            "title" => self.title = Some(value.to_string()),
+            "author" => self.author = Some(value.to_string()),
+            "par_time" => self.par_time = Some(parse_non_negative_number(value)?),
+            "next" => self.next = Some(value.to_string()),
+            "eye_distance" => self.eye_distance = Some(parse_non_negative_number(value)?),
+            "description" => self.description = Some(value.to_string()),
+            "player_speed" => self.player_speed = Some(parse_non_negative_number(value)?),
+            "charge_depletion" => self.charge_depletion = Some(parse_non_negative_number(value)?),
+            "recharge_amount" => self.recharge_amount = Some(parse_non_negative_number(value)?),
+            "recharge_cooldown" => self.recharge_cooldown = Some(parse_non_negative_number(value)?),
+            "gravity" => self.gravity = Some(parse_number(value)?),
+            _ => return Err((SpanPart::Key, Self::KEYS.to_vec())),
+        }
+        Ok(())
+    }
+}
+
+// The part of a metadata line an error refers to
+enum SpanPart {
+    Key,
+    Value,
+}
+
+#[derive(Clone, PartialEq)]
 pub struct ParsedLevel {
     cells: Vec<Vec<Cell>>,
+    metadata: LevelMetadata,
 }
 
 impl ParsedLevel {
     pub const MAX_LEVEL_WIDTH_AND_HEIGHT: usize = 256;
 
-    pub fn from(cells: Vec<Vec<Cell>>) -> anyhow::Result<Self> {
-        let level = Self { cells };
+    pub fn from(cells: Vec<Vec<Cell>>) -> Result<Self, Vec<CellError>> {
+        let level = Self {
+            cells,
+            metadata: LevelMetadata::default(),
+        };
 
         // =================================
         // Validate the level
@@ -51,55 +185,130 @@ impl ParsedLevel {
 
         let (width, height, _depth) = level.dimensions();
         if width > Self::MAX_LEVEL_WIDTH_AND_HEIGHT || height > Self::MAX_LEVEL_WIDTH_AND_HEIGHT {
-            return anyhow::bail!("Level is too large");
+            return Err(vec![CellError::new("Level is too large", None)]);
         }
 
-        let player_count = level
+        let mut errors = Vec::new();
+
+        let player_cells = level
             .iter_cells()
             .filter(|(_pos, cell)| {
                 cell.block_stack_iter()
                     .any(|(block_type, _id)| block_type == &Block::Player)
             })
-            .count();
-        if player_count != 1 {
-            return anyhow::bail!("Level must have exactly one player, found {}", player_count);
+            .map(|(pos, _cell)| pos)
+            .collect::<Vec<_>>();
+        if player_cells.is_empty() {
+            errors.push(CellError::new(
+                "Level must have exactly one player, found 0",
+                None,
+            ));
+        }
+        // Point at every player but the first one
+        for pos in player_cells.iter().skip(1) {
+            errors.push(CellError::new(
+                &format!(
+                    "Level must have exactly one player, found {}",
+                    player_cells.len()
+                ),
+                Some(*pos),
+            ));
         }
 
         // Check uniqueness of IDs
         let mut ids = HashSet::new();
-        for (_pos, cell) in level.iter_cells() {
+        for (pos, cell) in level.iter_cells() {
             for (_block_type, id) in cell.block_stack_iter() {
                 if let Some(id) = id {
                     if ids.contains(id) {
-                        return anyhow::bail!("Duplicate ID");
+                        errors.push(CellError::new(
+                            &format!("Duplicate ID #{}", id.id()),
+                            Some(pos),
+                        ));
                     }
                     ids.insert(id);
                 }
             }
         }
 
-        // Every door must reference an existing trigger
+        // Every trigger a door, platform or firework emitter references must exist
         let trigger_ids = level
             .iter_cells()
             .flat_map(|(_pos, cell)| {
                 cell.block_stack_iter()
                     .filter_map(|(block, id)| match block {
-                        Block::Trigger => id.clone(),
+                        Block::Trigger(_) => id.clone(),
                         _ => None,
                     })
             })
             .collect::<HashSet<_>>();
-        for (_pos, cell) in level.iter_cells() {
-            for (_block_type, _id) in cell.block_stack_iter() {
-                if let Block::Door(id) = _block_type {
+        for (pos, cell) in level.iter_cells() {
+            for (block, _id) in cell.block_stack_iter() {
+                for id in block.trigger_ids() {
                     if !trigger_ids.contains(id) {
-                        return anyhow::bail!("Door references non-existing trigger");
+                        errors.push(CellError::new(
+                            &format!(
+                                "{:?} references non-existing trigger #{}",
+                                block.get_block_type(),
+                                id.id()
+                            ),
+                            Some(pos),
+                        ));
                     }
                 }
             }
         }
 
-        Ok(level)
+        for (pos, cell) in level.iter_cells() {
+            for (block, _id) in cell.block_stack_iter() {
+                let message = match block {
+                    Block::PatrolEnemy(path, _) if path.speed == 0 => {
+                        "Patrol enemy speed must be at least 1"
+                    }
+                    Block::Platform(path) if path.speed == 0 => "Platform speed must be at least 1",
+                    _ => continue,
+                };
+                errors.push(CellError::new(message, Some(pos)));
+            }
+        }
+
+        // Teleporters come in pairs, the pair ID must be used by exactly two teleporters
+        let teleporters = level
+            .iter_cells()
+            .flat_map(|(pos, cell)| {
+                cell.block_stack_iter()
+                    .filter_map(move |(block, _id)| match block {
+                        Block::Teleporter(pair_id) => Some((pos, pair_id)),
+                        _ => None,
+                    })
+            })
+            .collect::<Vec<_>>();
+        for (pos, pair_id) in &teleporters {
+            let count = teleporters
+                .iter()
+                .filter(|(_pos, other_pair_id)| other_pair_id == pair_id)
+                .count();
+            if count != 2 {
+                errors.push(CellError::new(
+                    &format!(
+                        "Teleporter pair #{} must have exactly two teleporters, found {}",
+                        pair_id.id(),
+                        count
+                    ),
+                    Some(*pos),
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(level)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn metadata(&self) -> &LevelMetadata {
+        &self.metadata
     }
 
     /// Returns an iterator over all cells in the level.
@@ -134,6 +343,58 @@ impl ParsedLevel {
         (x, y, z)
     }
 
+    /// Converts the level back into level text.
+    /// Cells are separated by tabs, repeated blocks are compacted with the x<count> multiplicator
+    /// and only the IDs given in the level text are written.
+    pub fn to_level_string(&self) -> String {
+        let header = self
+            .metadata
+            .entries()
+            .into_iter()
+            .map(|(key, value)| format!("{}: {}", key, value));
+        let rows = self.cells.iter().map(|row| {
+            row.iter()
+                .map(|cell| self.cell_to_string(cell))
+                .collect::<Vec<_>>()
+                .join("\t")
+        });
+        header.chain(rows).collect::<Vec<_>>().join("\n")
+    }
+
+    fn cell_to_string(&self, cell: &Cell) -> String {
+        let mut blocks = Vec::new();
+        let mut block_stack = cell.block_stack.iter().peekable();
+        while let Some((block, id)) = block_stack.next() {
+            let block_string = block_to_string(block);
+            if let Some(id) = id {
+                blocks.push(format!("{}#{}", block_string, id.id()));
+                continue;
+            }
+
+            // Equal blocks without an explicit ID are compacted
+            let mut count = 1;
+            while count < MAX_MULTIPLICATOR
+                && block_stack
+                    .next_if(|(next_block, next_id)| next_block == block && next_id.is_none())
+                    .is_some()
+            {
+                count += 1;
+            }
+            if count > 1 {
+                blocks.push(format!("{}x{}", block_string, count));
+            } else {
+                blocks.push(block_string);
+            }
+        }
+
+        match (cell.is_glitch_area, blocks.is_empty()) {
+            (true, _) => format!("_{}", blocks.join("+")),
+            // A cell can not be empty in the level text, the parser never creates such a cell
+            (false, true) => block_to_string(&Block::Empty),
+            (false, false) => blocks.join("+"),
+        }
+    }
+
     /// Converts a level into raw RGBA8 data
     pub fn to_glitch_raw_rgba8(&self) -> Vec<u8> {
         // Create a new raw texture with the same dimensions as the level
@@ -164,16 +425,32 @@ impl ParsedLevel {
         blurred.to_rgba8().into_raw()
     }
 
+    /// Returns true if the level has a checkpoint with the given ID
+    pub(crate) fn has_checkpoint(&self, id: &Id) -> bool {
+        self.iter_cells().any(|((x, y), cell)| {
+            cell.block_stack_iter()
+                .enumerate()
+                .any(|(index, (block, block_id))| {
+                    block == &Block::Checkpoint
+                        && block_id
+                            .clone()
+                            .unwrap_or_else(|| Id::generated(x, y, index))
+                            == *id
+                })
+        })
+    }
+
     /// Converts the given block to a player block and the given player block to a Checkpoint block
     pub(crate) fn set_checkpoint(&mut self, id: Id) {
-        for (_pos, cell) in self.iter_cells_mut() {
-            for (block, block_id_opt) in cell.block_stack_iter_mut() {
-                if let Some(block_id) = block_id_opt {
-                    if *block_id == id {
-                        *block = Block::Player;
-                    } else if *block == Block::Player {
-                        *block = Block::Checkpoint;
-                    }
+        for ((x, y), cell) in self.iter_cells_mut() {
+            for (index, (block, block_id)) in cell.block_stack_iter_mut().enumerate() {
+                let block_id = block_id
+                    .clone()
+                    .unwrap_or_else(|| Id::generated(x, y, index));
+                if block_id == id {
+                    *block = Block::Player;
+                } else if *block == Block::Player {
+                    *block = Block::Checkpoint;
                 }
             }
         }
@@ -200,12 +477,40 @@ fn parse_id(input: &str) -> IResult<&str, Id> {
     Ok((rest, Id::try_from(id).unwrap()))
 }
 
-// A door is of the form D(<id>)
+// A door is of the form D(<condition>)
 fn parse_door(input: &str) -> IResult<&str, Block> {
     let (rest, _) = tag("D(")(input)?;
-    let (rest, id) = parse_id(rest)?;
+    let (rest, condition) = parse_door_condition(rest)?;
     let (rest, _) = tag(")")(rest)?;
-    Ok((rest, Block::Door(id)))
+    Ok((rest, Block::Door(condition)))
+}
+
+// A door condition combines trigger IDs with ! (not), & (and), | (or) and parentheses
+// & binds stronger than |, e.g. #a|#b&!#c is the same as #a|(#b&(!#c))
+fn parse_door_condition(input: &str) -> IResult<&str, DoorCondition> {
+    let (rest, mut conditions) = separated_list1(tag("|"), parse_door_conjunction)(input)?;
+    if conditions.len() == 1 {
+        return Ok((rest, conditions.remove(0)));
+    }
+    Ok((rest, DoorCondition::Or(conditions)))
+}
+
+fn parse_door_conjunction(input: &str) -> IResult<&str, DoorCondition> {
+    let (rest, mut conditions) = separated_list1(tag("&"), parse_door_operand)(input)?;
+    if conditions.len() == 1 {
+        return Ok((rest, conditions.remove(0)));
+    }
+    Ok((rest, DoorCondition::And(conditions)))
+}
+
+fn parse_door_operand(input: &str) -> IResult<&str, DoorCondition> {
+    alt((
+        map(preceded(tag("!"), parse_door_operand), |condition| {
+            DoorCondition::Not(Box::new(condition))
+        }),
+        delimited(tag("("), parse_door_condition, tag(")")),
+        map(parse_id, DoorCondition::Trigger),
+    ))(input)
 }
 
 // upper letter (A-Z)*
@@ -218,7 +523,109 @@ fn parse_goal(input: &str) -> IResult<&str, Block> {
     let (rest, _) = tag("G(")(input)?;
     let (rest, text) = parse_goal_text(rest)?;
     let (rest, _) = tag(")")(rest)?;
-    Ok((rest, Block::Goal(text)))
+    Ok((rest, Block::Goal(text, None)))
+}
+
+// The multiplicator has at most two digits
+const MAX_MULTIPLICATOR: usize = 99;
+
+// A trigger is T (momentary), TL (latching), TG (toggle) or T(<seconds>) (timed)
+fn parse_trigger(input: &str) -> IResult<&str, Block> {
+    let (rest, _) = tag("T")(input)?;
+    let (rest, mode) = opt(alt((
+        value(TriggerMode::Latching, tag("L")),
+        value(TriggerMode::Toggle, tag("G")),
+        map(
+            delimited(
+                tag("("),
+                take_while_m_n(1, 3, |c: char| c.is_ascii_digit()),
+                tag(")"),
+            ),
+            |seconds: &str| TriggerMode::Timed(seconds.parse().unwrap()),
+        ),
+    )))(rest)?;
+    Ok((rest, Block::Trigger(mode.unwrap_or(TriggerMode::Momentary))))
+}
+
+// A platform is of the form M<segments> with an optional condition, e.g. MX3, MY-2, MZ2(#t) or MX3Y-2
+// Every segment of the form <axis><distance> leads to the next waypoint
+fn parse_platform(input: &str) -> IResult<&str, Block> {
+    let (rest, _) = tag("M")(input)?;
+    let (rest, segments) = many1(parse_platform_segment)(rest)?;
+    let (rest, condition) = opt(delimited(tag("("), parse_door_condition, tag(")")))(rest)?;
+
+    Ok((
+        rest,
+        Block::Platform(PlatformPath {
+            segments,
+            condition,
+            speed: PlatformPath::DEFAULT_SPEED,
+        }),
+    ))
+}
+
+// A segment of a platform path moves along the x, y or z axis, e.g. X3 or Z-2
+fn parse_platform_segment(input: &str) -> IResult<&str, (Axis, i32)> {
+    let (rest, axis) = alt((
+        value(Axis::X, tag("X")),
+        value(Axis::Y, tag("Y")),
+        value(Axis::Z, tag("Z")),
+    ))(input)?;
+    let (rest, sign) = opt(tag("-"))(rest)?;
+    let (rest, distance) = take_while_m_n(1, 2, |c: char| c.is_ascii_digit())(rest)?;
+
+    let distance = distance.parse::<i32>().unwrap();
+    Ok((
+        rest,
+        (axis, if sign.is_some() { -distance } else { distance }),
+    ))
+}
+
+// A teleporter is of the form O(#<pair>), the two teleporters with the same pair ID are connected
+fn parse_teleporter(input: &str) -> IResult<&str, Block> {
+    let (rest, pair_id) = delimited(tag("O("), parse_id, tag(")"))(input)?;
+    Ok((rest, Block::Teleporter(pair_id)))
+}
+
+// A patrol enemy is of the form E3<mode>[<speed>](<segments>), e.g. E3L(X3,Y2,X-3,Y-2) or E3P6(Y4)
+// The mode is L (loop) or P (ping-pong) and the speed is given in cells per second
+fn parse_patrol_enemy(input: &str) -> IResult<&str, Block> {
+    let (rest, _) = tag("E3")(input)?;
+    let (rest, mode) = alt((
+        value(PatrolMode::Loop, tag("L")),
+        value(PatrolMode::PingPong, tag("P")),
+    ))(rest)?;
+    let (rest, speed) = opt(take_while_m_n(1, 2, |c: char| c.is_ascii_digit()))(rest)?;
+    let (rest, segments) = delimited(
+        tag("("),
+        separated_list1(tag(","), parse_patrol_segment),
+        tag(")"),
+    )(rest)?;
+
+    Ok((
+        rest,
+        Block::PatrolEnemy(
+            PatrolPath {
+                mode,
+                speed: speed.map_or(PatrolPath::DEFAULT_SPEED, |speed| speed.parse().unwrap()),
+                segments,
+            },
+            EnemyParams::default(),
+        ),
+    ))
+}
+
+// A segment of a patrol path walks along the x or y axis, e.g. X3 or Y-2
+fn parse_patrol_segment(input: &str) -> IResult<&str, (Axis, i32)> {
+    let (rest, axis) = alt((value(Axis::X, tag("X")), value(Axis::Y, tag("Y"))))(input)?;
+    let (rest, sign) = opt(tag("-"))(rest)?;
+    let (rest, distance) = take_while_m_n(1, 2, |c: char| c.is_ascii_digit())(rest)?;
+
+    let distance = distance.parse::<i32>().unwrap();
+    Ok((
+        rest,
+        (axis, if sign.is_some() { -distance } else { distance }),
+    ))
 }
 
 // a block can be multiplicated in the z direction by adding x<amount> to the end
@@ -229,9 +636,111 @@ fn parse_multiplicator(input: &str) -> IResult<&str, usize> {
     Ok((rest, multiplicator.parse::<usize>().unwrap()))
 }
 
+// Properties are of the form {key=value,...}, e.g. {speed=6,damage=20}
+fn parse_properties(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
+    delimited(
+        tag("{"),
+        separated_list1(
+            tag(","),
+            separated_pair(
+                take_while1(|c: char| c.is_ascii_lowercase() || c == '_'),
+                tag("="),
+                take_while1(|c: char| !matches!(c, ',' | '}') && !c.is_whitespace()),
+            ),
+        ),
+        tag("}"),
+    )(input)
+}
+
+// The blocks of a cell from bottom to top
+type BlockStack = Vec<(Block, Option<Id>)>;
+
+// A property that can not be applied to its block
+struct PropertyError<'a> {
+    // The offending key or value
+    found: &'a str,
+    expected: Vec<&'static str>,
+}
+
+// Parses the value of a property, on failure the expected tokens are returned
+fn parse_property_value(
+    property_type: PropertyType,
+    value: &str,
+) -> Result<PropertyValue, Vec<&'static str>> {
+    match property_type {
+        PropertyType::Int => value
+            .parse::<u32>()
+            .map(PropertyValue::Int)
+            .map_err(|_| vec!["<whole number>"]),
+        PropertyType::Float => match value.parse::<f32>() {
+            Ok(number) if number.is_finite() && number >= 0.0 => Ok(PropertyValue::Float(number)),
+            _ => Err(vec!["<non-negative number>"]),
+        },
+        PropertyType::Id => value
+            .strip_prefix('#')
+            .and_then(|id| Id::try_from(id).ok())
+            .map(PropertyValue::Id)
+            .ok_or_else(|| vec!["#<id>"]),
+        PropertyType::Enum(names) => names
+            .iter()
+            .find(|&&name| name == value)
+            .map(|&name| PropertyValue::Enum(name))
+            .ok_or_else(|| names.to_vec()),
+    }
+}
+
+// Applies the properties to the block according to its schema
+fn set_properties<'a>(
+    block: &mut Block,
+    properties: Vec<(&'a str, &'a str)>,
+) -> Result<(), PropertyError<'a>> {
+    let schema = block.property_schema();
+    for (key, value) in properties {
+        let Some(property) = schema.iter().find(|property| property.key == key) else {
+            let expected = if schema.is_empty() {
+                vec!["<no properties>"]
+            } else {
+                schema.iter().map(|property| property.key).collect()
+            };
+            return Err(PropertyError {
+                found: key,
+                expected,
+            });
+        };
+        let value = parse_property_value(property.property_type, value).map_err(|expected| {
+            PropertyError {
+                found: value,
+                expected,
+            }
+        })?;
+        block.set_property(key, value);
+    }
+    Ok(())
+}
+
+// Writes the properties that differ from their defaults, e.g. {speed=6,damage=20}
+fn properties_to_string(block: &Block) -> String {
+    let properties = block
+        .property_schema()
+        .iter()
+        .filter_map(|property| {
+            let value = block.property(property.key);
+            (value != property.default)
+                .then(|| value.map(|value| format!("{}={}", property.key, value)))
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+    if properties.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", properties.join(","))
+    }
+}
+
 // a block is always of the form of a single character and an optional ID
 // e.g. N, P, D, X, G, W, N#abc123, etc.
-fn parse_block(input: &str) -> IResult<&str, Vec<(Block, Option<Id>)>> {
+// Any block can have properties after it, e.g. E2X{speed=6} (see Block::property_schema)
+fn parse_block(input: &str) -> IResult<&str, Result<BlockStack, PropertyError<'_>>> {
     let (rest, block) = alt((
         value(Block::FloorNormal, tag("N")),
         value(Block::Player, tag("P")),
@@ -243,15 +752,31 @@ fn parse_block(input: &str) -> IResult<&str, Vec<(Block, Option<Id>)>> {
         value(Block::Box(BoxType::XAxis), tag("BX")),
         value(Block::Box(BoxType::YAxis), tag("BY")),
         value(Block::Box(BoxType::RotationFixed), tag("BRF")),
-        value(Block::Trigger, tag("T")),
+        parse_trigger,
         value(Block::Charge, tag("C")),
-        value(Block::StaticEnemy, tag("E1")),
-        value(Block::LinearEnemy(LinearEnemyDirection::XAxis), tag("E2X")),
-        value(Block::LinearEnemy(LinearEnemyDirection::YAxis), tag("E2Y")),
+        value(Block::StaticEnemy(EnemyParams::default()), tag("E1")),
+        value(
+            Block::LinearEnemy(LinearEnemyDirection::XAxis, EnemyParams::default()),
+            tag("E2X"),
+        ),
+        value(
+            Block::LinearEnemy(LinearEnemyDirection::YAxis, EnemyParams::default()),
+            tag("E2Y"),
+        ),
+        parse_patrol_enemy,
+        value(Block::ChasingEnemy(EnemyParams::default()), tag("E4")),
         value(Block::Checkpoint, tag("S")),
-        value(Block::FireworkEmitter, tag("F")),
+        value(Block::FireworkEmitter(None), tag("F")),
+        parse_platform,
+        parse_teleporter,
     ))(input)?;
 
+    let mut block = block;
+    let (rest, properties) = opt(parse_properties)(rest)?;
+    if let Err(error) = set_properties(&mut block, properties.unwrap_or_default()) {
+        return Ok((rest, Err(error)));
+    }
+
     let (rest, multiplicator) = opt(parse_multiplicator)(rest)?;
     // can only specify id if multiplicator is 1
     let (rest, id) = if multiplicator.is_none() || multiplicator.unwrap() == 1 {
@@ -261,58 +786,333 @@ fn parse_block(input: &str) -> IResult<&str, Vec<(Block, Option<Id>)>> {
     };
 
     // duplicate the block if multiplicator is specified
+    // blocks without an ID get a generated one when the level is loaded (see Cell::blocks_with_ids)
     let mut block_stack = Vec::new();
     for _ in 0..multiplicator.unwrap_or(1) {
-        block_stack.push((block.clone(), id.clone().or_else(|| Some(Id::random()))));
+        block_stack.push((block.clone(), id.clone()));
     }
 
-    Ok((rest, block_stack))
+    Ok((rest, Ok(block_stack)))
+}
+
+// The inverse of parse_block (without ID and multiplicator)
+fn block_to_string(block: &Block) -> String {
+    let token = match block {
+        Block::FloorNormal => "N".to_string(),
+        Block::Player => "P".to_string(),
+        Block::Door(condition) => format!("D({})", condition),
+        Block::Empty => "X".to_string(),
+        Block::Goal(text, _level) => format!("G({})", text),
+        Block::Wall => "W".to_string(),
+        Block::Box(BoxType::Free) => "BF".to_string(),
+        Block::Box(BoxType::XAxis) => "BX".to_string(),
+        Block::Box(BoxType::YAxis) => "BY".to_string(),
+        Block::Box(BoxType::RotationFixed) => "BRF".to_string(),
+        Block::Trigger(TriggerMode::Momentary) => "T".to_string(),
+        Block::Trigger(TriggerMode::Latching) => "TL".to_string(),
+        Block::Trigger(TriggerMode::Toggle) => "TG".to_string(),
+        Block::Trigger(TriggerMode::Timed(seconds)) => format!("T({})", seconds),
+        Block::Charge => "C".to_string(),
+        Block::StaticEnemy(_) => "E1".to_string(),
+        Block::LinearEnemy(LinearEnemyDirection::XAxis, _) => "E2X".to_string(),
+        Block::LinearEnemy(LinearEnemyDirection::YAxis, _) => "E2Y".to_string(),
+        Block::Checkpoint => "S".to_string(),
+        Block::FireworkEmitter(_) => "F".to_string(),
+        Block::Platform(path) => {
+            let segments = path
+                .segments
+                .iter()
+                .map(|(axis, distance)| format!("{:?}{}", axis, distance))
+                .collect::<String>();
+            match &path.condition {
+                Some(condition) => format!("M{}({})", segments, condition),
+                None => format!("M{}", segments),
+            }
+        }
+        Block::Teleporter(pair_id) => format!("O(#{})", pair_id.id()),
+        Block::PatrolEnemy(path, _) => {
+            let mode = match path.mode {
+                PatrolMode::Loop => "L",
+                PatrolMode::PingPong => "P",
+            };
+            let speed = if path.speed == PatrolPath::DEFAULT_SPEED {
+                String::new()
+            } else {
+                path.speed.to_string()
+            };
+            let segments = path
+                .segments
+                .iter()
+                .map(|(axis, distance)| format!("{:?}{}", axis, distance))
+                .collect::<Vec<_>>();
+            format!("E3{}{}({})", mode, speed, segments.join(","))
+        }
+        Block::ChasingEnemy(_) => "E4".to_string(),
+    };
+    format!("{}{}", token, properties_to_string(block))
 }
 
 // A cell is of the form [_](N|P|D|X|G|W|...)(#[a-zA-Z0-9]{1,10})?
-fn parse_cell(input: &str) -> IResult<&str, Cell> {
+fn parse_cell(input: &str) -> IResult<&str, Result<Cell, PropertyError<'_>>> {
     let (rest, glitch_area_tag) = opt(tag("_"))(input)?;
     let (rest, block_stack) = separated_list0(tag("+"), parse_block)(rest)?;
 
     let is_glitch_area = glitch_area_tag.is_some();
+    let block_stack = match block_stack.into_iter().collect::<Result<Vec<_>, _>>() {
+        Ok(block_stack) => block_stack,
+        Err(error) => return Ok((rest, Err(error))),
+    };
 
     Ok((
         rest,
-        Cell {
+        Ok(Cell {
             is_glitch_area,
             block_stack: block_stack.into_iter().flatten().collect(),
-        },
+        }),
     ))
 }
 
-fn parse_level_line(input: &str) -> IResult<&str, Vec<Cell>> {
-    separated_list1(space1, parse_cell)(input.trim())
+// A metadata line starts with a key of the form [a-z_]+ followed by a colon
+fn parse_metadata_key(input: &str) -> IResult<&str, &str> {
+    let (rest, key) = take_while1(|c: char| c.is_ascii_lowercase() || c == '_')(input)?;
+    let (rest, _) = tag(":")(rest)?;
+    Ok((rest, key))
+}
+
+// The blocks that can start a cell (after the optional glitch area tag "_")
+const BLOCK_TOKENS: [&str; 24] = [
+    "N",
+    "P",
+    "D(<condition>)",
+    "X",
+    "G(<TEXT>)",
+    "W",
+    "BF",
+    "BX",
+    "BY",
+    "BRF",
+    "T",
+    "TL",
+    "TG",
+    "T(<seconds>)",
+    "C",
+    "E1",
+    "E2X",
+    "E2Y",
+    "E3<mode>(<path>)",
+    "E4",
+    "S",
+    "F",
+    "M<segments>",
+    "O(#<pair>)",
+];
+
+// Parses a whole cell, that is everything between two whitespaces.
+// On failure the range of the unexpected part of the token and the tokens expected there are returned.
+fn parse_cell_token(token: &str) -> Result<Cell, (Range<usize>, Vec<&'static str>)> {
+    let rest = match parse_cell(token) {
+        Ok(("", Ok(cell))) => return Ok(cell),
+        Ok((_rest, Err(error))) => {
+            let offset = token.offset(error.found);
+            return Err((offset..offset + error.found.len(), error.expected));
+        }
+        Ok((rest, Ok(_cell))) => rest,
+        Err(_) => token,
+    };
+    let offset = token.len() - rest.len();
+
+    // A "+" is only consumed together with the block following it
+    if rest.starts_with('+') {
+        return Err((offset + 1..token.len(), BLOCK_TOKENS.to_vec()));
+    }
+    if offset == 0 {
+        let mut expected = vec!["_"];
+        expected.extend(BLOCK_TOKENS);
+        return Err((offset..token.len(), expected));
+    }
+    if &token[..offset] == "_" {
+        return Err((offset..token.len(), BLOCK_TOKENS.to_vec()));
+    }
+    Err((offset..token.len(), vec!["+", "#<id>", "x<count>"]))
+}
+
+// Splits a line into its cells, each with its byte offset in the line
+fn split_cells(line: &str) -> Vec<(usize, &str)> {
+    let mut cells = Vec::new();
+    let mut cell_start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), cell_start) {
+            (true, Some(start)) => {
+                cells.push((start, &line[start..i]));
+                cell_start = None;
+            }
+            (false, None) => cell_start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(start) = cell_start {
+        cells.push((start, &line[start..]));
+    }
+    cells
 }
 
 pub fn parse_level(input: &str) -> Result<ParsedLevel, LevelParseError> {
-    let (rest, parsed) = separated_list0(newline, parse_level_line)(input)
-        .map_err(|e| LevelParseError::ParseFailed {
-            rest: input.to_string(),
-        })?;
+    let mut cells = Vec::new();
+    // The source span of every cell, used to point at cells that fail validation
+    let mut cell_spans = Vec::new();
+    let mut syntax_errors = Vec::new();
 
-    if rest.len() > 0 {
+    let mut metadata = LevelMetadata::default();
+    // Metadata lines are only allowed before the first row of the grid
+    let mut in_header = true;
+    // Empty lines after the first row, they are errors if another row follows as they would shift the rows
+    let mut empty_lines = Vec::new();
+
+    let mut line_start = 0;
+    for (line_index, line) in input.split('\n').enumerate() {
+        let trimmed_line = line.trim_start();
+        if in_header {
+            if let Ok((value, key)) = parse_metadata_key(trimmed_line) {
+                let key_start = line_start + line.len() - trimmed_line.len();
+                if let Err((span_part, expected)) = metadata.set(key, value.trim()) {
+                    let (start, found) = match span_part {
+                        SpanPart::Key => (key_start, key),
+                        SpanPart::Value => (
+                            line_start + line.len() - value.trim_start().len(),
+                            value.trim(),
+                        ),
+                    };
+                    syntax_errors.push(SyntaxError {
+                        span: SourceSpan::new(
+                            input,
+                            line_index,
+                            line_start,
+                            start,
+                            start + found.len(),
+                        ),
+                        expected: expected.into_iter().map(String::from).collect(),
+                        found: found.to_string(),
+                    });
+                }
+                line_start += line.len() + 1;
+                continue;
+            }
+        }
+
+        if !trimmed_line.is_empty() {
+            in_header = false;
+        }
+
+        let mut row = Vec::new();
+        let mut row_spans = Vec::new();
+        for (offset, token) in split_cells(line) {
+            let start = line_start + offset;
+            let end = start + token.len();
+            match parse_cell_token(token) {
+                Ok(cell) => {
+                    row.push(cell);
+                    row_spans.push(SourceSpan::new(input, line_index, line_start, start, end));
+                }
+                Err((error_range, expected)) => syntax_errors.push(SyntaxError {
+                    span: SourceSpan::new(
+                        input,
+                        line_index,
+                        line_start,
+                        start + error_range.start,
+                        start + error_range.end,
+                    ),
+                    expected: expected.into_iter().map(String::from).collect(),
+                    found: token[error_range].to_string(),
+                }),
+            }
+        }
+
+        // Empty lines before the grid and at its end are skipped
+        if trimmed_line.is_empty() {
+            if !cells.is_empty() {
+                let mut expected = vec!["_"];
+                expected.extend(BLOCK_TOKENS);
+                empty_lines.push(SyntaxError {
+                    span: SourceSpan::new(
+                        input,
+                        line_index,
+                        line_start,
+                        line_start,
+                        line_start + line.len(),
+                    ),
+                    expected: expected.into_iter().map(String::from).collect(),
+                    found: String::new(),
+                });
+            }
+        } else {
+            syntax_errors.append(&mut empty_lines);
+            cells.push(row);
+            cell_spans.push(row_spans);
+        }
+        line_start += line.len() + 1;
+    }
+
+    if !syntax_errors.is_empty() {
         return Err(LevelParseError::ParseFailed {
-            rest: rest.to_string(),
+            errors: syntax_errors,
         });
     }
 
-    match ParsedLevel::from(parsed) {
-        Ok(level) => Ok(level),
-        Err(e) => Err(LevelParseError::ValidationError {
-            message: e.to_string(),
-        }),
+    let mut level =
+        ParsedLevel::from(cells).map_err(|errors| LevelParseError::ValidationError {
+            errors: errors
+                .into_iter()
+                .map(|error| ValidationIssue {
+                    message: error.message,
+                    span: error
+                        .cell
+                        .map(|(x, y)| cell_spans[y as usize][x as usize].clone()),
+                })
+                .collect(),
+        })?;
+    level.metadata = metadata;
+    Ok(level)
+}
+
+// A location in the level source
+// line and column start at 1, start and end are byte offsets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl SourceSpan {
+    fn new(input: &str, line_index: usize, line_start: usize, start: usize, end: usize) -> Self {
+        Self {
+            line: line_index + 1,
+            column: input[line_start..start].chars().count() + 1,
+            start,
+            end,
+        }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyntaxError {
+    pub span: SourceSpan,
+    pub expected: Vec<String>,
+    pub found: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub message: String,
+    // The offending cell, None if the issue concerns the whole level
+    pub span: Option<SourceSpan>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LevelParseError {
-    ParseFailed { rest: String },
-    ValidationError { message: String },
+    ParseFailed { errors: Vec<SyntaxError> },
+    ValidationError { errors: Vec<ValidationIssue> },
 }
 
 impl std::fmt::Display for LevelParseError {
@@ -326,6 +1126,8 @@ impl std::fmt::Display for LevelParseError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::object_types::IdOrigin;
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     fn test_parse_level() {
@@ -335,7 +1137,8 @@ mod tests {
                 cells: vec![vec![Cell {
                     is_glitch_area: false,
                     block_stack: vec![(Block::FloorNormal, None), (Block::Player, None)]
-                }]]
+                }]],
+                metadata: LevelMetadata::default(),
             }
         );
         assert_eq!(
@@ -344,7 +1147,8 @@ mod tests {
                 cells: vec![vec![Cell {
                     is_glitch_area: false,
                     block_stack: vec![(Block::FloorNormal, None), (Block::Player, None)]
-                }]]
+                }]],
+                metadata: LevelMetadata::default(),
             }
         );
         assert_eq!(
@@ -353,7 +1157,8 @@ mod tests {
                 cells: vec![vec![Cell {
                     is_glitch_area: false,
                     block_stack: vec![(Block::FloorNormal, None), (Block::Player, None)]
-                }]]
+                }]],
+                metadata: LevelMetadata::default(),
             }
         );
         assert_eq!(
@@ -388,7 +1193,8 @@ mod tests {
                             block_stack: vec![(Block::FloorNormal, None)]
                         }
                     ]
-                ]
+                ],
+                metadata: LevelMetadata::default(),
             }
         );
 
@@ -424,8 +1230,679 @@ mod tests {
                             block_stack: vec![(Block::FloorNormal, None)]
                         }
                     ]
+                ],
+                metadata: LevelMetadata::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_generated_ids() {
+        let level = parse_level("N+P N+S\nN+S N+T#t").unwrap();
+        let ids = level
+            .iter_cells()
+            .flat_map(|(pos, cell)| cell.blocks_with_ids(pos).map(|(_block, id)| id))
+            .collect::<Vec<_>>();
+        assert_eq!(ids[3], Id::generated(1, 0, 1));
+        assert_eq!(ids[7], Id::try_from("t").unwrap());
+        assert_eq!(
+            ids.iter().map(|id| id.origin()).collect::<Vec<_>>(),
+            [vec![IdOrigin::Generated; 7], vec![IdOrigin::User]].concat()
+        );
+
+        // Generated IDs are only visible when the level is loaded
+        assert!(level
+            .iter_cells()
+            .flat_map(|(_pos, cell)| cell.block_stack_iter())
+            .all(|(block, id)| id.is_some() == matches!(block, Block::Trigger(_))));
+    }
+
+    #[test]
+    fn test_parse_errors_have_spans() {
+        let errors = match parse_level("N+P N+Q\nN\tNx2+ N+P") {
+            Err(LevelParseError::ParseFailed { errors }) => errors,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        assert_eq!(errors.len(), 2);
+
+        // "+Q" can not be parsed, the block after the "+" is missing
+        assert_eq!(
+            errors[0].span,
+            SourceSpan {
+                line: 1,
+                column: 7,
+                start: 6,
+                end: 7
+            }
+        );
+        assert_eq!(errors[0].found, "Q");
+        assert!(errors[0].expected.contains(&String::from("BRF")));
+
+        assert_eq!(
+            errors[1].span,
+            SourceSpan {
+                line: 2,
+                column: 7,
+                start: 14,
+                end: 14
+            }
+        );
+        assert_eq!(errors[1].found, "");
+    }
+
+    #[test]
+    fn test_validation_errors_point_at_cells() {
+        let errors = match parse_level("N+P N+T#a\nN+D(#b) N+P N+W#a") {
+            Err(LevelParseError::ValidationError { errors }) => errors,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let spans = errors
+            .iter()
+            .map(|error| error.span.clone().map(|span| (span.line, span.column)))
+            .collect::<Vec<_>>();
+        // The extra player, the duplicate ID and the door without trigger
+        assert_eq!(spans, vec![Some((2, 9)), Some((2, 13)), Some((2, 1))]);
+
+        match parse_level("N N") {
+            Err(LevelParseError::ValidationError { errors }) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].span, None);
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_door_conditions() {
+        let trigger = |id: &str| DoorCondition::Trigger(Id::try_from(id).unwrap());
+        let door = |level: &str| match &parse_level(level).unwrap().cells[0][0].block_stack[1] {
+            (Block::Door(condition), _id) => condition.clone(),
+            other => panic!("Unexpected block {:?}", other),
+        };
+        let triggers = "\tN+T#a+P N+T#b N+T#c";
+
+        assert_eq!(door(&format!("N+D(#a){}", triggers)), trigger("a"));
+        assert_eq!(
+            door(&format!("N+D(#a|#b&!#c){}", triggers)),
+            DoorCondition::Or(vec![
+                trigger("a"),
+                DoorCondition::And(vec![
+                    trigger("b"),
+                    DoorCondition::Not(Box::new(trigger("c")))
+                ])
+            ])
+        );
+        assert_eq!(
+            door(&format!("N+D(!(#a|#b)&#c){}", triggers)),
+            DoorCondition::And(vec![
+                DoorCondition::Not(Box::new(DoorCondition::Or(vec![
+                    trigger("a"),
+                    trigger("b")
+                ]))),
+                trigger("c")
+            ])
+        );
+
+        // Every referenced trigger has to exist
+        match parse_level("N+D(#a&!#x|#y) N+T#a+P") {
+            Err(LevelParseError::ValidationError { errors }) => assert_eq!(
+                errors
+                    .iter()
+                    .map(|error| error.message.as_str())
+                    .collect::<Vec<_>>(),
+                vec![
+                    "Door references non-existing trigger #x",
+                    "Door references non-existing trigger #y"
                 ]
+            ),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(parse_level("N+D(#a&) N+T#a+P").is_err());
+    }
+
+    #[test]
+    fn test_parse_trigger_modes() {
+        let level = parse_level("N+P+T N+TL N+TG N+T(15)x2").unwrap();
+        let modes = level
+            .iter_cells()
+            .flat_map(|(_pos, cell)| cell.block_stack_iter())
+            .filter_map(|(block, _id)| match block {
+                Block::Trigger(mode) => Some(*mode),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            modes,
+            vec![
+                TriggerMode::Momentary,
+                TriggerMode::Latching,
+                TriggerMode::Toggle,
+                TriggerMode::Timed(15),
+                TriggerMode::Timed(15)
+            ]
+        );
+        assert!(parse_level("N+P N+T()").is_err());
+    }
+
+    #[test]
+    fn test_parse_platforms() {
+        let level = parse_level("N+P N+MX3 N+MY-2x2 N+MZ12(#t|#u) N+MX2Y-1Z1 N+T#t N+T#u").unwrap();
+        let platforms = level
+            .iter_cells()
+            .flat_map(|(_pos, cell)| cell.block_stack_iter())
+            .filter_map(|(block, _id)| match block {
+                Block::Platform(path) => Some((path.segments.clone(), path.condition.is_some())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            platforms,
+            vec![
+                (vec![(Axis::X, 3)], false),
+                (vec![(Axis::Y, -2)], false),
+                (vec![(Axis::Y, -2)], false),
+                (vec![(Axis::Z, 12)], true),
+                (vec![(Axis::X, 2), (Axis::Y, -1), (Axis::Z, 1)], false)
+            ]
+        );
+
+        match parse_level("N+P N+MZ2(#x)") {
+            Err(LevelParseError::ValidationError { errors }) => assert_eq!(
+                errors[0].message,
+                "Platform references non-existing trigger #x"
+            ),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(parse_level("N+P N+MW2").is_err());
+    }
+
+    #[test]
+    fn test_parse_teleporters() {
+        let level = parse_level("N+P N+O(#a)\nN+O(#a)#b N").unwrap();
+        let teleporters = level
+            .iter_cells()
+            .flat_map(|(pos, cell)| {
+                cell.block_stack_iter()
+                    .filter(|(block, _id)| matches!(block, Block::Teleporter(_)))
+                    .map(move |(block, id)| (pos, block.clone(), id.clone()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            teleporters,
+            vec![
+                ((1, 0), Block::Teleporter(Id::try_from("a").unwrap()), None),
+                (
+                    (0, 1),
+                    Block::Teleporter(Id::try_from("a").unwrap()),
+                    Some(Id::try_from("b").unwrap())
+                ),
+            ]
+        );
+
+        match parse_level("N+P N+O(#a) N+O(#a) N+O(#a) N+O(#b)") {
+            Err(LevelParseError::ValidationError { errors }) => {
+                let messages = errors
+                    .iter()
+                    .map(|error| {
+                        let column = error.span.as_ref().map(|span| span.column);
+                        (error.message.as_str(), column)
+                    })
+                    .collect::<Vec<_>>();
+                let three_a = "Teleporter pair #a must have exactly two teleporters, found 3";
+                assert_eq!(
+                    messages,
+                    vec![
+                        (three_a, Some(5)),
+                        (three_a, Some(13)),
+                        (three_a, Some(21)),
+                        (
+                            "Teleporter pair #b must have exactly two teleporters, found 1",
+                            Some(29)
+                        ),
+                    ]
+                );
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(parse_level("N+P N+O(a)").is_err());
+    }
+
+    #[test]
+    fn test_parse_patrol_enemies() {
+        let level = parse_level("N+P N+E3L(X3,Y2,X-3,Y-2) N+E3P12(Y-4)").unwrap();
+        let paths = level
+            .iter_cells()
+            .flat_map(|(_pos, cell)| cell.block_stack_iter())
+            .filter_map(|(block, _id)| match block {
+                Block::PatrolEnemy(path, _) => Some(path.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                PatrolPath {
+                    mode: PatrolMode::Loop,
+                    speed: PatrolPath::DEFAULT_SPEED,
+                    segments: vec![(Axis::X, 3), (Axis::Y, 2), (Axis::X, -3), (Axis::Y, -2)],
+                },
+                PatrolPath {
+                    mode: PatrolMode::PingPong,
+                    speed: 12,
+                    segments: vec![(Axis::Y, -4)],
+                },
+            ]
+        );
+        assert_eq!(
+            paths[0].waypoints(),
+            vec![(0, 0), (3, 0), (3, 2), (0, 2), (0, 0)]
+        );
+
+        match parse_level("N+P N+E3P0(X2)") {
+            Err(LevelParseError::ValidationError { errors }) => {
+                assert_eq!(errors[0].message, "Patrol enemy speed must be at least 1")
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(parse_level("N+P N+E3L(Z2)").is_err());
+        assert!(parse_level("N+P N+E3L()").is_err());
+    }
+
+    #[test]
+    fn test_parse_metadata_header() {
+        let level = parse_level(
+            "title: First Steps\n  author: someone\npar_time: 12.5\neye_distance: 2\n\nN+P N\nN N",
+        )
+        .unwrap();
+        assert_eq!(
+            level.metadata(),
+            &LevelMetadata {
+                title: Some("First Steps".to_string()),
+                author: Some("someone".to_string()),
+                par_time: Some(12.5),
+                eye_distance: Some(2.0),
+                ..Default::default()
+            }
+        );
+        assert_eq!(level.dimensions(), (2, 2, 2));
+
+        // Headerless levels have no metadata
+        assert_eq!(
+            parse_level("N+P N").unwrap().metadata(),
+            &LevelMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_metadata_errors() {
+        let errors = match parse_level("titel: x\npar_time: fast\nN+P\ntitle: late") {
+            Err(LevelParseError::ParseFailed { errors }) => errors,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let found = errors
+            .iter()
+            .map(|error| (error.span.line, error.span.column, error.found.as_str()))
+            .collect::<Vec<_>>();
+        // Metadata after the grid is parsed as a cell
+        assert_eq!(
+            found,
+            vec![
+                (1, 1, "titel"),
+                (2, 11, "fast"),
+                (4, 1, "title:"),
+                (4, 8, "late")
+            ]
+        );
+        assert_eq!(
+            errors[1].expected,
+            vec!["<non-negative number>".to_string()]
+        );
+
+        // Times and distances are finite and not negative
+        let errors = match parse_level(
+            "par_time: NaN\neye_distance: NaN\neye_distance: inf\npar_time: -1\n\nN+P",
+        ) {
+            Err(LevelParseError::ParseFailed { errors }) => errors,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let found = errors
+            .iter()
+            .map(|error| (error.found.as_str(), error.expected.clone()))
+            .collect::<Vec<_>>();
+        let expected = vec!["<non-negative number>".to_string()];
+        assert_eq!(
+            found,
+            vec![
+                ("NaN", expected.clone()),
+                ("NaN", expected.clone()),
+                ("inf", expected.clone()),
+                ("-1", expected),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_line_in_grid() {
+        // Empty lines before and after the grid are fine
+        assert!(parse_level("title: Gap\n\nN+P N\nN N\n\n").is_ok());
+
+        let errors = match parse_level("N+P N\n  \n\nN N") {
+            Err(LevelParseError::ParseFailed { errors }) => errors,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let spans = errors
+            .iter()
+            .map(|error| {
+                (
+                    error.span.line,
+                    error.span.column,
+                    error.span.start,
+                    error.span.end,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec![(2, 1, 6, 8), (3, 1, 9, 9)]);
+    }
+
+    #[test]
+    fn test_parse_game_rules_header() {
+        let level =
+            parse_level("gravity: -4.5\nplayer_speed: 10\ncharge_depletion: 2\n\nN+P").unwrap();
+        assert_eq!(
+            level.metadata(),
+            &LevelMetadata {
+                player_speed: Some(10.0),
+                charge_depletion: Some(2.0),
+                gravity: Some(-4.5),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            level.to_level_string(),
+            "player_speed: 10\ncharge_depletion: 2\ngravity: -4.5\nN+P"
+        );
+
+        let errors = match parse_level("recharge_cooldown: -1\n\nN+P") {
+            Err(LevelParseError::ParseFailed { errors }) => errors,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        assert_eq!(errors[0].found, "-1");
+        assert_eq!(
+            errors[0].expected,
+            vec!["<non-negative number>".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_enemy_params() {
+        let level = parse_level(
+            "N+P N+E2X{speed=6,damage=20} N+E1{stun=0.5}x2 N+E2Y N+E3P(X2){knockback=8} N+E4{damage=5}",
+        )
+        .unwrap();
+        let params = level
+            .iter_cells()
+            .flat_map(|(_pos, cell)| cell.block_stack_iter())
+            .filter_map(|(block, _id)| match block {
+                Block::StaticEnemy(params)
+                | Block::LinearEnemy(_, params)
+                | Block::PatrolEnemy(_, params)
+                | Block::ChasingEnemy(params) => Some(*params),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let default = EnemyParams::default();
+        assert_eq!(
+            params,
+            vec![
+                EnemyParams {
+                    speed: 6.0,
+                    damage: 20.0,
+                    ..default
+                },
+                EnemyParams {
+                    stun: 0.5,
+                    ..default
+                },
+                EnemyParams {
+                    stun: 0.5,
+                    ..default
+                },
+                default,
+                EnemyParams {
+                    knockback: 8.0,
+                    ..default
+                },
+                EnemyParams {
+                    damage: 5.0,
+                    ..default
+                },
+            ]
+        );
+        assert_eq!(
+            level.to_level_string(),
+            "N+P\tN+E2X{damage=20,speed=6}\tN+E1{stun=0.5}x2\tN+E2Y\tN+E3P(X2){knockback=8}\tN+E4{damage=5}"
+        );
+
+        // Patrol and chasing enemies walk at their own speed
+        let errors =
+            match parse_level("N+P E1{speed=2} E2X{damage=-1} E2Y{turn_delay=0.5} E4{speed=2}") {
+                Err(LevelParseError::ParseFailed { errors }) => errors,
+                other => panic!("Unexpected result {:?}", other),
+            };
+        let found = errors
+            .iter()
+            .map(|error| {
+                (
+                    error.span.column,
+                    error.found.as_str(),
+                    error.expected.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect::<Vec<(_, _, Vec<_>)>>();
+        assert_eq!(
+            found,
+            vec![
+                (8, "speed", vec!["damage", "knockback", "stun"]),
+                (28, "-1", vec!["<non-negative number>"]),
+                (47, "0.5", vec!["<whole number>"]),
+                (55, "speed", vec!["damage", "knockback", "stun"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_block_properties() {
+        let level = parse_level("N+P N+MX2(#t){speed=5} N+F{trigger=#t} N+T#t").unwrap();
+        let blocks = level
+            .iter_cells()
+            .flat_map(|(_pos, cell)| cell.block_stack_iter())
+            .map(|(block, _id)| block.clone())
+            .collect::<Vec<_>>();
+        let t = Id::try_from("t").unwrap();
+        assert_eq!(
+            blocks[3],
+            Block::Platform(PlatformPath {
+                segments: vec![(Axis::X, 2)],
+                condition: Some(DoorCondition::Trigger(t.clone())),
+                speed: 5,
+            })
+        );
+        assert_eq!(blocks[5], Block::FireworkEmitter(Some(t)));
+
+        let goal_level = parse_level("N+P N+G(NEXT){level=#second}").unwrap();
+        assert!(goal_level.iter_cells().any(|(_pos, cell)| cell
+            .block_stack_iter()
+            .any(|(block, _id)| block
+                == &Block::Goal("NEXT".to_string(), Some(Id::try_from("second").unwrap())))));
+        assert_eq!(
+            level.to_level_string(),
+            "N+P\tN+MX2(#t){speed=5}\tN+F{trigger=#t}\tN+T#t"
+        );
+
+        let errors = match parse_level("N+P N{a=1} E2X{start=up} F{trigger=t} MX2{speed=-1}") {
+            Err(LevelParseError::ParseFailed { errors }) => errors,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let found = errors
+            .iter()
+            .map(|error| {
+                (
+                    error.found.as_str(),
+                    error.expected.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect::<Vec<(_, Vec<_>)>>();
+        assert_eq!(
+            found,
+            vec![
+                ("a", vec!["<no properties>"]),
+                ("up", vec!["forward", "backward"]),
+                ("t", vec!["#<id>"]),
+                ("-1", vec!["<whole number>"]),
+            ]
+        );
+
+        match parse_level("N+P N+F{trigger=#x} N+MX2{speed=0}") {
+            Err(LevelParseError::ValidationError { errors }) => {
+                let messages = errors
+                    .iter()
+                    .map(|error| error.message.as_str())
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    messages,
+                    vec![
+                        "FireworkEmitter references non-existing trigger #x",
+                        "Platform speed must be at least 1",
+                    ]
+                );
             }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_to_level_string() {
+        let level =
+            parse_level("title: Doors\n\nN+N+N+T#t  _N+P\n_  D(#t)x2+BF#box+BF+BF").unwrap();
+        assert_eq!(
+            level.to_level_string(),
+            "title: Doors\nNx3+T#t\t_N+P\n_\tD(#t)x2+BF#box+BFx2"
         );
+
+        // More than 99 equal blocks need multiple multiplicators
+        let level = parse_level(&format!("P+{}", ["Nx50"; 3].join("+"))).unwrap();
+        assert_eq!(level.to_level_string(), "P+Nx99+Nx51");
+    }
+
+    // Generates a random valid level text with explicit and generated IDs
+    fn random_level_text(rng: &mut StdRng) -> String {
+        use rand::seq::SliceRandom;
+        use rand::Rng;
+
+        let tokens = [
+            "N",
+            "X",
+            "G(END)",
+            "W",
+            "BF",
+            "BX",
+            "BY",
+            "BRF",
+            "T",
+            "TL",
+            "TG",
+            "T(12)",
+            "MX2",
+            "MZ-1(#door)",
+            "MY3(!#door)",
+            "MX2Y-1Z3",
+            "C",
+            "E1",
+            "E2X",
+            "E2Y",
+            "E3L(X2,Y-1)",
+            "E3P6(Y3)",
+            "E4",
+            "E1{damage=20}",
+            "E3L(X2,Y-1){damage=5,stun=1}",
+            "E4{knockback=8}",
+            "E2X{speed=6,damage=2.5}",
+            "E2Y{turn_delay=5,stun=0}",
+            "E2X{start=backward}",
+            "MY-2{speed=3}",
+            "F{trigger=#door}",
+            "G(NEXT){level=#second}",
+            "S",
+            "F",
+            "D(#door)",
+            "D(!#door)",
+            "D(#door&(#door|!#door))",
+            "D(!(#door&#door))",
+        ];
+        let width = rng.gen_range(1..6);
+        let height = rng.gen_range(1..6);
+        let player = (rng.gen_range(0..width), rng.gen_range(0..height));
+        let mut next_id = 0;
+
+        let mut lines = Vec::new();
+        if rng.gen_bool(0.5) {
+            lines.push("title: Random Level".to_string());
+            lines.push("par_time: 7.25".to_string());
+        }
+        for y in 0..height {
+            let mut cells = Vec::new();
+            for x in 0..width {
+                let mut blocks = Vec::new();
+                // Every level has the trigger the doors refer to
+                if (x, y) == (0, 0) {
+                    blocks.push("T#door".to_string());
+                    blocks.push("O(#gate)".to_string());
+                }
+                // The partner of the teleporter above
+                if (x, y) == (width - 1, height - 1) {
+                    blocks.push("O(#gate)".to_string());
+                }
+                for _ in 0..rng.gen_range(0..5) {
+                    let token = tokens.choose(rng).unwrap();
+                    let block = match rng.gen_range(0..4) {
+                        0 => {
+                            next_id += 1;
+                            format!("{}#id{}", token, next_id)
+                        }
+                        1 => format!("{}x{}", token, rng.gen_range(1..4)),
+                        _ => token.to_string(),
+                    };
+                    blocks.push(block);
+                }
+                if (x, y) == player {
+                    blocks.push("P".to_string());
+                }
+
+                let glitch_area = rng.gen_bool(0.3);
+                if blocks.is_empty() && !glitch_area {
+                    blocks.push("N".to_string());
+                }
+                let prefix = if glitch_area { "_" } else { "" };
+                cells.push(format!("{}{}", prefix, blocks.join("+")));
+            }
+            lines.push(cells.join(" "));
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_level_string_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let text = random_level_text(&mut rng);
+            let level = parse_level(&text).unwrap();
+            let serialized = level.to_level_string();
+            assert_eq!(parse_level(&serialized).unwrap(), level, "{}", text);
+            // The serialized text is canonical
+            assert_eq!(
+                parse_level(&serialized).unwrap().to_level_string(),
+                serialized
+            );
+        }
     }
 }
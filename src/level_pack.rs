@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    level_compressor,
+    level_loader::{parse_level, ParsedLevel},
+    object_types::{Block, Id},
+    save_state::content_key,
+};
+
+// Increase when the level pack format changes in an incompatible way
+pub const LEVEL_PACK_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackLevel {
+    // Goals refer to the level with this ID, e.g. G(END){level=#id}
+    pub id: String,
+    // The level text, title and other metadata are given in its header
+    pub level: String,
+}
+
+/// An ordered list of levels that are played one after another.
+/// The first level is unlocked from the start, a goal unlocks the level it leads to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelPack {
+    pub version: u32,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub levels: Vec<PackLevel>,
+}
+
+impl LevelPack {
+    /// Serializes the pack into a compressed string that can be shared in an URL
+    pub fn to_compressed_string(&self) -> String {
+        level_compressor::compress_level(&serde_json::to_string(self).unwrap())
+    }
+
+    pub fn from_compressed_string(compressed: &str) -> Result<Self, String> {
+        let json = level_compressor::decompress_level(compressed)?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let pack: LevelPack = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        pack.validate()?;
+        Ok(pack)
+    }
+
+    // Checks that the IDs are unique, every level parses and every goal leads to a level of the pack
+    fn validate(&self) -> Result<(), String> {
+        if self.version != LEVEL_PACK_VERSION {
+            return Err(format!("Unsupported level pack version {}", self.version));
+        }
+        if self.levels.is_empty() {
+            return Err("Level pack has no levels".to_string());
+        }
+
+        let mut ids = HashSet::new();
+        for pack_level in &self.levels {
+            Id::try_from(pack_level.id.as_str())
+                .map_err(|_| format!("Invalid level ID #{}", pack_level.id))?;
+            if !ids.insert(pack_level.id.as_str()) {
+                return Err(format!("Duplicate level ID #{}", pack_level.id));
+            }
+        }
+
+        for pack_level in &self.levels {
+            let level = parse_level(&pack_level.level)
+                .map_err(|e| format!("Level #{}: {}", pack_level.id, e))?;
+            for (_pos, cell) in level.iter_cells() {
+                for (block, _id) in cell.block_stack_iter() {
+                    if let Block::Goal(_, Some(target)) = block {
+                        if !ids.contains(target.id()) {
+                            return Err(format!(
+                                "Level #{}: Goal leads to the unknown level #{}",
+                                pack_level.id,
+                                target.id()
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the parsed level with the given ID, None if the pack has no such level
+    pub fn level(&self, id: &str) -> Option<ParsedLevel> {
+        let pack_level = self.levels.iter().find(|level| level.id == id)?;
+        // The levels were validated when the pack was created
+        Some(parse_level(&pack_level.level).expect("Level pack level must be valid"))
+    }
+
+    /// Identifies the pack in the save state
+    pub fn key(&self) -> String {
+        content_key(&serde_json::to_string(self).unwrap())
+    }
+
+    pub fn first_level_id(&self) -> &str {
+        &self.levels[0].id
+    }
+
+    /// Returns the ID of the level after the given one, None for the last level
+    pub fn next_level_id(&self, id: &str) -> Option<&str> {
+        let index = self.levels.iter().position(|level| level.id == id)?;
+        self.levels.get(index + 1).map(|level| level.id.as_str())
+    }
+}
+
+/// The levels of a pack the player has unlocked and completed, in the order they were reached
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelPackProgress {
+    pub unlocked: Vec<String>,
+    pub completed: Vec<String>,
+}
+
+impl LevelPackProgress {
+    pub fn new(pack: &LevelPack) -> Self {
+        Self {
+            unlocked: vec![pack.first_level_id().to_string()],
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.iter().any(|unlocked| unlocked == id)
+    }
+
+    pub fn unlock(&mut self, id: &str) {
+        if !self.is_unlocked(id) {
+            self.unlocked.push(id.to_string());
+        }
+    }
+
+    pub fn complete(&mut self, id: &str) {
+        if !self.completed.iter().any(|completed| completed == id) {
+            self.completed.push(id.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(levels: &[(&str, &str)]) -> LevelPack {
+        LevelPack {
+            version: LEVEL_PACK_VERSION,
+            title: Some("Pack".to_string()),
+            author: None,
+            levels: levels
+                .iter()
+                .map(|(id, level)| PackLevel {
+                    id: id.to_string(),
+                    level: level.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_level_pack_compression_roundtrip() {
+        let pack = pack(&[
+            ("first", "title: First\n\nN+P N+G(GO)"),
+            ("second", "N+P N+G(BACK){level=#first}"),
+        ]);
+        let compressed = pack.to_compressed_string();
+        assert_eq!(
+            LevelPack::from_compressed_string(&compressed),
+            Ok(pack.clone())
+        );
+
+        assert_eq!(pack.first_level_id(), "first");
+        assert_eq!(pack.next_level_id("first"), Some("second"));
+        assert_eq!(pack.next_level_id("second"), None);
+        assert_eq!(
+            pack.level("first").unwrap().metadata().title,
+            Some("First".to_string())
+        );
+        assert!(pack.level("third").is_none());
+    }
+
+    #[test]
+    fn test_level_pack_validation() {
+        assert_eq!(
+            pack(&[]).validate(),
+            Err("Level pack has no levels".to_string())
+        );
+        assert_eq!(
+            pack(&[("a", "N+P"), ("a", "N+P")]).validate(),
+            Err("Duplicate level ID #a".to_string())
+        );
+        assert_eq!(
+            pack(&[("a", "N+P N+G(GO){level=#b}")]).validate(),
+            Err("Level #a: Goal leads to the unknown level #b".to_string())
+        );
+        assert!(pack(&[("a", "N")]).validate().is_err());
+        assert!(pack(&[("a b", "N+P")]).validate().is_err());
+    }
+}
@@ -20,16 +20,25 @@ use winit::{
 };
 
 mod command;
-mod game;
+pub mod game;
+pub mod game_events;
+pub mod game_status;
 mod game_objects;
 mod level_compressor;
-mod level_loader;
+pub mod level_loader;
+pub mod level_pack;
 mod mesh;
 mod model;
-mod object_types;
+pub mod object_types;
+mod pathfinding;
 mod physics;
+pub mod replay;
+pub mod rewind;
+pub mod save_state;
+pub mod solver;
 mod stereo_camera;
 mod texture;
+pub mod world_snapshot;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -357,15 +366,34 @@ impl State {
             (BlockType::Charge, charge_model),
             (BlockType::StaticEnemy, static_enemy_model),
             (BlockType::LinearEnemy, linear_enemy_model),
+            (BlockType::PatrolEnemy, linear_enemy_model),
+            (BlockType::ChasingEnemy, static_enemy_model),
             (BlockType::Checkpoint, checkpoint_model),
             (BlockType::Ghost, ghost_model),
             (BlockType::Cube, cube_model),
+            (BlockType::Platform, floor_model),
+            (BlockType::Teleporter, trigger_model),
         ].into_iter().fold(HashMap::new(), |mut acc, (block_type, model_handle)| {
             acc.entry(block_type).or_insert_with(Vec::new).push(model_handle);
             acc
         });
 
-        let game_world = game::GameWorld::new(handle_store);
+        let mut game_world = game::GameWorld::new(handle_store);
+        // The page takes the events with take_game_events(), the native app only logs them
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                game_world.set_event_callback(|event| {
+                    command::GAME_EVENTS.lock().unwrap().push(event.clone())
+                });
+            } else {
+                game_world.set_event_callback(|event| log::info!("Game event {:?}", event));
+            }
+        }
+        // The page imports its save state with import_save_state()
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(save_state) = save_state::SaveState::read_from_file() {
+            game_world.set_save_state(save_state);
+        }
 
         let stereo_camera = game_world.get_camera();
         let mut stereo_camera_uniform = stereo_camera::StereoCameraUniform::new();
@@ -658,6 +686,18 @@ impl State {
         }
     }
 
+    // Shows the glitch area of the level that is played
+    fn write_glitch_area_texture(&mut self) {
+        if let Some(level) = self.game_world.level() {
+            self.glitch_area_texture.write_rgba8(
+                &self.queue,
+                &level.to_glitch_raw_rgba8(),
+                ParsedLevel::MAX_LEVEL_WIDTH_AND_HEIGHT as u32 * 4,
+                ParsedLevel::MAX_LEVEL_WIDTH_AND_HEIGHT as u32 * 4,
+            );
+        }
+    }
+
     fn update(&mut self) {
         while let Some(command) = command::COMMANDS.pop() {
             log::debug!("Processing command: {:?}", command);
@@ -672,7 +712,7 @@ impl State {
                     self.game_world.load_level(parsed_level);
                 }
                 command::Command::SetEyeDistance(distance) => {
-                    self.game_world.set_eye_distance(distance);
+                    self.game_world.set_eye_distance_setting(distance);
                 }
                 command::Command::SetSize(width, height, scale_factor) => {
                     self.resize(
@@ -689,6 +729,57 @@ impl State {
                 command::Command::ActionButtonReleased => {
                     self.game_world.release_player_grab_action();
                 }
+                command::Command::StartRecording => {
+                    self.game_world.start_recording();
+                }
+                command::Command::StopRecording => {
+                    *command::RECORDED_REPLAY.lock().unwrap() = self
+                        .game_world
+                        .stop_recording()
+                        .map(|replay| replay.to_compressed_string());
+                }
+                command::Command::PlayReplay(parsed_level, replay) => {
+                    self.glitch_area_texture.write_rgba8(
+                        &self.queue,
+                        &parsed_level.to_glitch_raw_rgba8(),
+                        ParsedLevel::MAX_LEVEL_WIDTH_AND_HEIGHT as u32 * 4,
+                        ParsedLevel::MAX_LEVEL_WIDTH_AND_HEIGHT as u32 * 4,
+                    );
+                    self.game_world.play_replay(parsed_level, replay);
+                }
+                command::Command::SetGameRules(game_rules) => {
+                    self.game_world.set_game_rules(game_rules);
+                }
+                command::Command::LoadLevelPack(level_pack) => {
+                    match self.game_world.load_level_pack(level_pack) {
+                        Ok(()) => self.write_glitch_area_texture(),
+                        Err(e) => log::warn!("Failed to load the level pack: {}", e),
+                    }
+                }
+                command::Command::ImportSaveState(save_state) => {
+                    self.game_world.set_save_state(save_state);
+                }
+                command::Command::QuickSave => {
+                    self.game_world.quick_save();
+                }
+                command::Command::QuickLoad => {
+                    self.game_world.quick_load();
+                }
+                command::Command::RewindButtonPressed => {
+                    self.game_world.start_rewind();
+                }
+                command::Command::RewindButtonReleased => {
+                    self.game_world.stop_rewind();
+                }
+                command::Command::UndoBoxPush => {
+                    self.game_world.undo_box_push();
+                }
+                command::Command::LoadPackLevel(id) => {
+                    match self.game_world.load_pack_level(&id) {
+                        Ok(()) => self.write_glitch_area_texture(),
+                        Err(e) => log::warn!("Failed to load level #{}: {}", id, e),
+                    }
+                }
             }
         }
 
@@ -715,6 +806,18 @@ impl State {
         }
 
         self.game_world.update();
+        *command::GAME_STATUS.lock().unwrap() = self.game_world.game_status().clone();
+        if self.game_world.take_save_state_changed() {
+            cfg_if::cfg_if! {
+                if #[cfg(target_arch = "wasm32")] {
+                    *command::SAVE_STATE.lock().unwrap() = self.game_world.save_state().clone();
+                } else {
+                    if let Err(e) = self.game_world.save_state().write_to_file() {
+                        log::warn!("Failed to write the save state: {}", e);
+                    }
+                }
+            }
+        }
 
         for mesh_handle in self.model_store.iter_handles() {
             let instances = self.game_world.iter_instances(mesh_handle);
@@ -866,11 +969,15 @@ pub async fn run() {
                             ..
                         } => match &physical_key {
                             PhysicalKey::Code(key_code) => {
-                                if state.key_pressed.insert(*key_code)
-                                    && (key_code == &KeyCode::Enter
-                                    || key_code == &KeyCode::Space)
-                                {
-                                    state.game_world.player_grab_action()
+                                // Repeated presses of a held key are ignored
+                                let first_press = state.key_pressed.insert(*key_code);
+                                match key_code {
+                                    KeyCode::Enter | KeyCode::Space if first_press => {
+                                        state.game_world.player_grab_action()
+                                    }
+                                    KeyCode::KeyR => state.game_world.start_rewind(),
+                                    KeyCode::KeyZ if first_press => state.game_world.undo_box_push(),
+                                    _ => {}
                                 }
                             }
                             _ => {}
@@ -889,6 +996,8 @@ pub async fn run() {
                                     || key_code == &KeyCode::Space)
                                 {
                                     state.game_world.release_player_grab_action()
+                                } else if key_code == &KeyCode::KeyR {
+                                    state.game_world.stop_rewind()
                                 }
                             }
                             _ => {}
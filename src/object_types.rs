@@ -1,11 +1,22 @@
-use rand::Rng;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone)]
 pub struct Id {
     id: String,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IdOrigin {
+    /// The ID was given in the level text
+    User,
+    /// The block had no ID in the level text
+    Generated,
+}
+
 impl Id {
+    const GENERATED_PREFIX: char = '~';
+
     pub fn new(id: String) -> Self {
         Self { id }
     }
@@ -14,15 +25,21 @@ impl Id {
         &self.id
     }
 
-    pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
-        let id: String = std::iter::repeat(())
-            .map(|()| rng.sample(rand::distributions::Alphanumeric))
-            .take(10)
-            .map(char::from)
-            .collect();
+    /// Creates the ID of the block at the given index of the block stack at (x, y).
+    /// Generated IDs start with a character that is not allowed in the level text,
+    /// so they never collide with IDs given by the user.
+    pub fn generated(x: i32, y: i32, index: usize) -> Self {
+        Self {
+            id: format!("{}{}_{}_{}", Self::GENERATED_PREFIX, x, y, index),
+        }
+    }
 
-        Self { id }
+    pub fn origin(&self) -> IdOrigin {
+        if self.id.starts_with(Self::GENERATED_PREFIX) {
+            IdOrigin::Generated
+        } else {
+            IdOrigin::User
+        }
     }
 }
 
@@ -51,20 +68,353 @@ impl TryFrom<&str> for Id {
 pub enum Block {
     FloorNormal,
     Player,
-    /// A door can be opened by a trigger with the given ID
-    Door(Id),
+    /// A door is open while the condition over the triggers is satisfied
+    Door(DoorCondition),
     Empty,
-    Goal(String),
+    /// A goal shows the text as a level, inside a level pack it leads to the level with the ID or the next level
+    Goal(String, Option<Id>),
     Wall,
     Box(BoxType),
-    Trigger,
+    Trigger(TriggerMode),
     Charge,
-    StaticEnemy,
-    LinearEnemy(LinearEnemyDirection),
+    StaticEnemy(EnemyParams),
+    LinearEnemy(LinearEnemyDirection, EnemyParams),
     Checkpoint,
-    FireworkEmitter,
+    /// Launches fireworks, only while the trigger with the given ID is active if there is one
+    FireworkEmitter(Option<Id>),
+    /// A kinematic platform that moves back and forth along its path
+    Platform(PlatformPath),
+    /// Teleports to the other teleporter with the same pair ID
+    Teleporter(Id),
+    /// An enemy that walks along a path of waypoints
+    PatrolEnemy(PatrolPath, EnemyParams),
+    /// An enemy that chases the player through the glitch area
+    ChasingEnemy(EnemyParams),
+}
+
+/// A boolean expression over trigger IDs, e.g. #a, #a&#b, #a|#b or !#a
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub enum DoorCondition {
+    /// Satisfied while the trigger with the given ID is pressed
+    Trigger(Id),
+    Not(Box<DoorCondition>),
+    And(Vec<DoorCondition>),
+    Or(Vec<DoorCondition>),
+}
+
+impl DoorCondition {
+    pub fn is_satisfied(&self, is_pressed: &impl Fn(&Id) -> bool) -> bool {
+        match self {
+            DoorCondition::Trigger(id) => is_pressed(id),
+            DoorCondition::Not(condition) => !condition.is_satisfied(is_pressed),
+            DoorCondition::And(conditions) => conditions.iter().all(|c| c.is_satisfied(is_pressed)),
+            DoorCondition::Or(conditions) => conditions.iter().any(|c| c.is_satisfied(is_pressed)),
+        }
+    }
+
+    fn fmt_operand(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        needs_parentheses: fn(&DoorCondition) -> bool,
+    ) -> std::fmt::Result {
+        if needs_parentheses(self) {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    /// Returns all trigger IDs the condition depends on
+    pub fn trigger_ids(&self) -> Vec<&Id> {
+        match self {
+            DoorCondition::Trigger(id) => vec![id],
+            DoorCondition::Not(condition) => condition.trigger_ids(),
+            DoorCondition::And(conditions) | DoorCondition::Or(conditions) => {
+                conditions.iter().flat_map(|c| c.trigger_ids()).collect()
+            }
+        }
+    }
+}
+
+// Writes the condition in the level text syntax
+// Nested conditions are put in parentheses where needed to be parsed into the same structure again
+impl std::fmt::Display for DoorCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DoorCondition::Trigger(id) => write!(f, "#{}", id.id()),
+            DoorCondition::Not(condition) => {
+                write!(f, "!")?;
+                condition.fmt_operand(f, |c| {
+                    matches!(c, DoorCondition::And(_) | DoorCondition::Or(_))
+                })
+            }
+            DoorCondition::And(conditions) => {
+                for (i, condition) in conditions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "&")?;
+                    }
+                    condition.fmt_operand(f, |c| {
+                        matches!(c, DoorCondition::And(_) | DoorCondition::Or(_))
+                    })?;
+                }
+                Ok(())
+            }
+            DoorCondition::Or(conditions) => {
+                for (i, condition) in conditions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    condition.fmt_operand(f, |c| matches!(c, DoorCondition::Or(_)))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// How a trigger reacts to being pressed
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum TriggerMode {
+    /// Active while something is on the trigger
+    Momentary,
+    /// Stays active once it was pressed
+    Latching,
+    /// Every press flips the state
+    Toggle,
+    /// Stays active for the given number of seconds after the release
+    Timed(u32),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct PlatformPath {
+    /// Each segment moves the given number of cells along an axis to the next waypoint, starting at the cell of the platform.
+    /// Negative distances move into the opposite direction.
+    pub segments: Vec<(Axis, i32)>,
+    /// If set the platform moves to the end while the condition is satisfied and back to the start otherwise.
+    /// Without a condition it moves back and forth all the time.
+    pub condition: Option<DoorCondition>,
+    /// Cells per second
+    pub speed: u32,
+}
+
+impl PlatformPath {
+    pub const DEFAULT_SPEED: u32 = 2;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum PatrolMode {
+    /// Walks from the last waypoint straight back to the first one
+    Loop,
+    /// Walks the waypoints backwards after reaching the last one
+    PingPong,
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct PatrolPath {
+    pub mode: PatrolMode,
+    /// Cells per second
+    pub speed: u32,
+    /// Each segment walks the given number of cells along the x or y axis, starting at the cell of the enemy
+    pub segments: Vec<(Axis, i32)>,
+}
+
+impl PatrolPath {
+    pub const DEFAULT_SPEED: u32 = 4;
+
+    /// Returns the waypoints as (x, y) cell offsets from the start, the first waypoint is the start itself
+    pub fn waypoints(&self) -> Vec<(i32, i32)> {
+        let mut waypoint = (0, 0);
+        let mut waypoints = vec![waypoint];
+        for (axis, distance) in &self.segments {
+            match axis {
+                Axis::X => waypoint.0 += distance,
+                Axis::Y => waypoint.1 += distance,
+                Axis::Z => {}
+            }
+            waypoints.push(waypoint);
+        }
+        waypoints
+    }
+}
+
+/// The tunable parameters of an enemy, set in the level text with {key=value,...}
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EnemyParams {
+    /// The charge the player loses per tick while touching the enemy
+    pub damage: f32,
+    /// Cells per second, only used by linear enemies
+    pub speed: f32,
+    /// The speed the player is pushed away with after a hit
+    pub knockback: f32,
+    /// Seconds the player can not move after a hit
+    pub stun: f32,
+    /// Ticks a stuck linear enemy waits before it turns around
+    pub turn_delay: u32,
+    /// The direction a linear enemy moves in first
+    pub start: StartDirection,
+}
+
+impl EnemyParams {
+    pub const DEFAULT: EnemyParams = EnemyParams {
+        damage: 10.0,
+        speed: 4.0,
+        knockback: 5.0,
+        stun: 0.2,
+        turn_delay: 20,
+        start: StartDirection::Forward,
+    };
+}
+
+impl Default for EnemyParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+// The parameters are always finite numbers, so equality is reflexive
+impl Eq for EnemyParams {}
+
+impl Hash for EnemyParams {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.damage.to_bits().hash(state);
+        self.speed.to_bits().hash(state);
+        self.knockback.to_bits().hash(state);
+        self.stun.to_bits().hash(state);
+        self.turn_delay.hash(state);
+        self.start.hash(state);
+    }
+}
+
+/// The direction a linear enemy starts to move in along its axis
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum StartDirection {
+    /// Into the positive direction of the world axis
+    Forward,
+    Backward,
+}
+
+impl StartDirection {
+    pub const NAMES: [&'static str; 2] = ["forward", "backward"];
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES[*self as usize]
+    }
+}
+
+/// The type of the value of a block property
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PropertyType {
+    /// A whole number that is not negative, e.g. 3
+    Int,
+    /// A finite number that is not negative, e.g. 2.5
+    Float,
+    /// The ID of another block, e.g. #t
+    Id,
+    /// One of the given names
+    Enum(&'static [&'static str]),
+}
+
+/// The value of a block property.
+/// Properties are written after the block, e.g. E2X{speed=6,start=backward}
+#[derive(Debug, PartialEq, Clone)]
+pub enum PropertyValue {
+    Int(u32),
+    Float(f32),
+    Id(Id),
+    Enum(&'static str),
+}
+
+impl Display for PropertyValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropertyValue::Int(value) => write!(f, "{}", value),
+            PropertyValue::Float(value) => write!(f, "{}", value),
+            PropertyValue::Id(id) => write!(f, "#{}", id.id()),
+            PropertyValue::Enum(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A property a block accepts
+#[derive(Debug)]
+pub struct PropertySchema {
+    pub key: &'static str,
+    pub property_type: PropertyType,
+    /// The value of the property if it is not given, None if the property is optional
+    pub default: Option<PropertyValue>,
+}
+
+impl PropertySchema {
+    const fn new(
+        key: &'static str,
+        property_type: PropertyType,
+        default: Option<PropertyValue>,
+    ) -> Self {
+        Self {
+            key,
+            property_type,
+            default,
+        }
+    }
 }
 
+const DAMAGE: PropertySchema = PropertySchema::new(
+    "damage",
+    PropertyType::Float,
+    Some(PropertyValue::Float(EnemyParams::DEFAULT.damage)),
+);
+const KNOCKBACK: PropertySchema = PropertySchema::new(
+    "knockback",
+    PropertyType::Float,
+    Some(PropertyValue::Float(EnemyParams::DEFAULT.knockback)),
+);
+const STUN: PropertySchema = PropertySchema::new(
+    "stun",
+    PropertyType::Float,
+    Some(PropertyValue::Float(EnemyParams::DEFAULT.stun)),
+);
+
+// The properties of each block in the order they are written
+// Static, patrol and chasing enemies only have the properties of their damage area
+static DAMAGE_AREA_PROPERTIES: [PropertySchema; 3] = [DAMAGE, KNOCKBACK, STUN];
+static LINEAR_ENEMY_PROPERTIES: [PropertySchema; 6] = [
+    DAMAGE,
+    PropertySchema::new(
+        "speed",
+        PropertyType::Float,
+        Some(PropertyValue::Float(EnemyParams::DEFAULT.speed)),
+    ),
+    KNOCKBACK,
+    STUN,
+    PropertySchema::new(
+        "turn_delay",
+        PropertyType::Int,
+        Some(PropertyValue::Int(EnemyParams::DEFAULT.turn_delay)),
+    ),
+    PropertySchema::new(
+        "start",
+        PropertyType::Enum(&StartDirection::NAMES),
+        Some(PropertyValue::Enum("forward")),
+    ),
+];
+static PLATFORM_PROPERTIES: [PropertySchema; 1] = [PropertySchema::new(
+    "speed",
+    PropertyType::Int,
+    Some(PropertyValue::Int(PlatformPath::DEFAULT_SPEED)),
+)];
+static FIREWORK_EMITTER_PROPERTIES: [PropertySchema; 1] =
+    [PropertySchema::new("trigger", PropertyType::Id, None)];
+// The ID of a level in the level pack, not of a block
+static GOAL_PROPERTIES: [PropertySchema; 1] =
+    [PropertySchema::new("level", PropertyType::Id, None)];
+
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub enum LinearEnemyDirection{
     XAxis,
@@ -97,6 +447,42 @@ pub enum BlockType {
     Ghost,
     Cube,
     FireworkEmitter,
+    Platform,
+    Teleporter,
+    PatrolEnemy,
+    ChasingEnemy,
+}
+
+impl BlockType {
+    /// Iterates over all block types
+    pub fn iter() -> impl Iterator<Item = BlockType> {
+        std::iter::successors(Some(BlockType::FloorNormal), BlockType::next)
+    }
+
+    // The exhaustive match makes sure that a new block type can not be left out of iter()
+    fn next(&self) -> Option<BlockType> {
+        match self {
+            BlockType::FloorNormal => Some(BlockType::Player),
+            BlockType::Player => Some(BlockType::Door),
+            BlockType::Door => Some(BlockType::Empty),
+            BlockType::Empty => Some(BlockType::Goal),
+            BlockType::Goal => Some(BlockType::Wall),
+            BlockType::Wall => Some(BlockType::Box),
+            BlockType::Box => Some(BlockType::Trigger),
+            BlockType::Trigger => Some(BlockType::Charge),
+            BlockType::Charge => Some(BlockType::StaticEnemy),
+            BlockType::StaticEnemy => Some(BlockType::LinearEnemy),
+            BlockType::LinearEnemy => Some(BlockType::Checkpoint),
+            BlockType::Checkpoint => Some(BlockType::Ghost),
+            BlockType::Ghost => Some(BlockType::Cube),
+            BlockType::Cube => Some(BlockType::FireworkEmitter),
+            BlockType::FireworkEmitter => Some(BlockType::Platform),
+            BlockType::Platform => Some(BlockType::Teleporter),
+            BlockType::Teleporter => Some(BlockType::PatrolEnemy),
+            BlockType::PatrolEnemy => Some(BlockType::ChasingEnemy),
+            BlockType::ChasingEnemy => None,
+        }
+    }
 }
 
 impl Block {
@@ -106,15 +492,19 @@ impl Block {
             Block::Player => BlockType::Player,
             Block::Door(_) => BlockType::Door,
             Block::Empty => BlockType::Empty,
-            Block::Goal(_) => BlockType::Goal,
+            Block::Goal(..) => BlockType::Goal,
             Block::Wall => BlockType::Wall,
             Block::Box(_) => BlockType::Box,
-            Block::Trigger => BlockType::Trigger,
+            Block::Trigger(_) => BlockType::Trigger,
             Block::Charge => BlockType::Charge,
-            Block::StaticEnemy => BlockType::StaticEnemy,
-            Block::LinearEnemy(_) => BlockType::LinearEnemy,
+            Block::StaticEnemy(_) => BlockType::StaticEnemy,
+            Block::LinearEnemy(..) => BlockType::LinearEnemy,
             Block::Checkpoint => BlockType::Checkpoint,
-            Block::FireworkEmitter => BlockType::FireworkEmitter,
+            Block::FireworkEmitter(_) => BlockType::FireworkEmitter,
+            Block::Platform(_) => BlockType::Platform,
+            Block::Teleporter(_) => BlockType::Teleporter,
+            Block::PatrolEnemy(..) => BlockType::PatrolEnemy,
+            Block::ChasingEnemy(_) => BlockType::ChasingEnemy,
         }
     }
 
@@ -124,15 +514,134 @@ impl Block {
             Block::Player => 1.0,
             Block::Door(_) => 1.0,
             Block::Empty => 1.0,
-            Block::Goal(_) => 1.0,
+            Block::Goal(..) => 1.0,
             Block::Wall => 1.0,
             Block::Box(_) => 1.0,
-            Block::Trigger => 0.0001,
+            Block::Trigger(_) => 0.0001,
             Block::Charge => 1.0,
-            Block::StaticEnemy => 1.0,
-            Block::LinearEnemy(_) => 1.0,
+            Block::StaticEnemy(_) => 1.0,
+            Block::LinearEnemy(..) => 1.0,
             Block::Checkpoint => 1.0,
-            Block::FireworkEmitter => 0.0,
+            Block::FireworkEmitter(_) => 0.0,
+            Block::Platform(_) => 1.0,
+            Block::Teleporter(_) => 0.0001,
+            Block::PatrolEnemy(..) => 1.0,
+            Block::ChasingEnemy(_) => 1.0,
+        }
+    }
+
+    /// Returns the IDs of the triggers the block reacts to
+    pub fn trigger_ids(&self) -> Vec<&Id> {
+        match self {
+            Block::Door(condition) => condition.trigger_ids(),
+            Block::Platform(path) => path
+                .condition
+                .iter()
+                .flat_map(|condition| condition.trigger_ids())
+                .collect(),
+            Block::FireworkEmitter(trigger) => trigger.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The properties the block accepts in the order they are written
+    pub fn property_schema(&self) -> &'static [PropertySchema] {
+        match self {
+            Block::StaticEnemy(_) | Block::PatrolEnemy(..) | Block::ChasingEnemy(_) => {
+                &DAMAGE_AREA_PROPERTIES
+            }
+            Block::LinearEnemy(..) => &LINEAR_ENEMY_PROPERTIES,
+            Block::Platform(_) => &PLATFORM_PROPERTIES,
+            Block::FireworkEmitter(_) => &FIREWORK_EMITTER_PROPERTIES,
+            Block::Goal(..) => &GOAL_PROPERTIES,
+            _ => &[],
+        }
+    }
+
+    /// Returns the value of a property of the schema, None if an optional property is not set
+    pub fn property(&self, key: &str) -> Option<PropertyValue> {
+        match (self, key) {
+            (
+                Block::StaticEnemy(params)
+                | Block::LinearEnemy(_, params)
+                | Block::PatrolEnemy(_, params)
+                | Block::ChasingEnemy(params),
+                "damage",
+            ) => Some(PropertyValue::Float(params.damage)),
+            (
+                Block::StaticEnemy(params)
+                | Block::LinearEnemy(_, params)
+                | Block::PatrolEnemy(_, params)
+                | Block::ChasingEnemy(params),
+                "knockback",
+            ) => Some(PropertyValue::Float(params.knockback)),
+            (
+                Block::StaticEnemy(params)
+                | Block::LinearEnemy(_, params)
+                | Block::PatrolEnemy(_, params)
+                | Block::ChasingEnemy(params),
+                "stun",
+            ) => Some(PropertyValue::Float(params.stun)),
+            (Block::LinearEnemy(_, params), "speed") => Some(PropertyValue::Float(params.speed)),
+            (Block::LinearEnemy(_, params), "turn_delay") => {
+                Some(PropertyValue::Int(params.turn_delay))
+            }
+            (Block::LinearEnemy(_, params), "start") => {
+                Some(PropertyValue::Enum(params.start.name()))
+            }
+            (Block::Platform(path), "speed") => Some(PropertyValue::Int(path.speed)),
+            (Block::FireworkEmitter(trigger), "trigger") => trigger.clone().map(PropertyValue::Id),
+            (Block::Goal(_, level), "level") => level.clone().map(PropertyValue::Id),
+            _ => None,
+        }
+    }
+
+    /// Sets a property of the schema, values that do not match the type of the schema are ignored
+    pub fn set_property(&mut self, key: &str, value: PropertyValue) {
+        match (self, key, value) {
+            (
+                Block::StaticEnemy(params)
+                | Block::LinearEnemy(_, params)
+                | Block::PatrolEnemy(_, params)
+                | Block::ChasingEnemy(params),
+                "damage",
+                PropertyValue::Float(damage),
+            ) => params.damage = damage,
+            (
+                Block::StaticEnemy(params)
+                | Block::LinearEnemy(_, params)
+                | Block::PatrolEnemy(_, params)
+                | Block::ChasingEnemy(params),
+                "knockback",
+                PropertyValue::Float(knockback),
+            ) => params.knockback = knockback,
+            (
+                Block::StaticEnemy(params)
+                | Block::LinearEnemy(_, params)
+                | Block::PatrolEnemy(_, params)
+                | Block::ChasingEnemy(params),
+                "stun",
+                PropertyValue::Float(stun),
+            ) => params.stun = stun,
+            (Block::LinearEnemy(_, params), "speed", PropertyValue::Float(speed)) => {
+                params.speed = speed
+            }
+            (Block::LinearEnemy(_, params), "turn_delay", PropertyValue::Int(turn_delay)) => {
+                params.turn_delay = turn_delay
+            }
+            (Block::LinearEnemy(_, params), "start", PropertyValue::Enum(name)) => {
+                params.start = if name == StartDirection::Backward.name() {
+                    StartDirection::Backward
+                } else {
+                    StartDirection::Forward
+                }
+            }
+            (Block::Platform(path), "speed", PropertyValue::Int(speed)) => path.speed = speed,
+            (Block::FireworkEmitter(trigger), "trigger", PropertyValue::Id(id)) => {
+                *trigger = Some(id)
+            }
+            (Block::Goal(_, level), "level", PropertyValue::Id(id)) => *level = Some(id),
+            _ => {}
         }
     }
 }
\ No newline at end of file
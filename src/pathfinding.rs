@@ -0,0 +1,221 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use bevy_ecs::system::Resource;
+
+use crate::level_loader::ParsedLevel;
+use crate::object_types::Block;
+
+// A position in the level of the form (x, y, level)
+// The level is the index in the block stack, an enemy on level 1 stands on a block of level 0
+pub type GridPosition = (i32, i32, i32);
+
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+#[derive(Clone, Copy, PartialEq)]
+enum NavCell {
+    Free,
+    Solid,
+    Door,
+}
+
+/// The static cells of a level that enemies walk on.
+/// Enemies walk from cell to cell on the same level, they can not jump, climb or fall.
+/// Objects that move (boxes, platforms, other enemies) are ignored.
+#[derive(Resource, Default)]
+pub struct NavGrid {
+    width: i32,
+    height: i32,
+    columns: Vec<Vec<NavCell>>,
+}
+
+impl NavGrid {
+    pub fn from_level(level: &ParsedLevel) -> Self {
+        let (width, height, _depth) = level.dimensions();
+        let mut columns = vec![Vec::new(); width * height];
+        for ((x, y), cell) in level.iter_cells() {
+            let mut column = Vec::new();
+            let mut z: f32 = 0.0;
+            for (block, _id) in cell.block_stack_iter() {
+                // Flat blocks like triggers do not occupy a level
+                if block.block_height() >= 1.0 {
+                    let nav_cell = match block {
+                        Block::FloorNormal | Block::Wall | Block::StaticEnemy(_) => NavCell::Solid,
+                        Block::Door(_) => NavCell::Door,
+                        _ => NavCell::Free,
+                    };
+                    let level = z.round() as usize;
+                    column.resize(level + 1, NavCell::Free);
+                    column[level] = nav_cell;
+                }
+                z += block.block_height();
+            }
+            columns[y as usize * width + x as usize] = column;
+        }
+
+        Self {
+            width: width as i32,
+            height: height as i32,
+            columns,
+        }
+    }
+
+    // Whether the given position is filled, doors are filled while they are closed
+    fn is_blocked(
+        &self,
+        (x, y, level): GridPosition,
+        is_door_open: &impl Fn(GridPosition) -> bool,
+    ) -> bool {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height || level < 0 {
+            return false;
+        }
+        let column = &self.columns[(y * self.width + x) as usize];
+        match column.get(level as usize) {
+            Some(NavCell::Solid) => true,
+            Some(NavCell::Door) => !is_door_open((x, y, level)),
+            Some(NavCell::Free) | None => false,
+        }
+    }
+
+    /// Whether an enemy can stand at the given position, that is the position is free and the one below is not
+    pub fn is_walkable(
+        &self,
+        (x, y, level): GridPosition,
+        is_door_open: &impl Fn(GridPosition) -> bool,
+    ) -> bool {
+        x >= 0
+            && y >= 0
+            && x < self.width
+            && y < self.height
+            && !self.is_blocked((x, y, level), is_door_open)
+            && self.is_blocked((x, y, level - 1), is_door_open)
+    }
+
+    /// Finds a shortest path with A* from start to goal on the level of the start.
+    /// The path contains the positions after the start up to and including the goal.
+    /// Returns None if the goal can not be reached.
+    pub fn find_path(
+        &self,
+        start: GridPosition,
+        goal: GridPosition,
+        is_door_open: &impl Fn(GridPosition) -> bool,
+    ) -> Option<Vec<GridPosition>> {
+        if start.2 != goal.2 || !self.is_walkable(goal, is_door_open) {
+            return None;
+        }
+        if start == goal {
+            return Some(Vec::new());
+        }
+        let heuristic = |(x, y, _level): GridPosition| (x - goal.0).abs() + (y - goal.1).abs();
+
+        let mut came_from = HashMap::new();
+        let mut cost = HashMap::from([(start, 0)]);
+        // Ties are broken by the position so that the same path is found every time
+        let mut open = BinaryHeap::from([Reverse((heuristic(start), start))]);
+        while let Some(Reverse((_estimate, position))) = open.pop() {
+            if position == goal {
+                let mut path = vec![goal];
+                while let Some(&previous) = came_from.get(path.last().unwrap()) {
+                    if previous == start {
+                        break;
+                    }
+                    path.push(previous);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let next_cost = cost[&position] + 1;
+            for (dx, dy) in DIRECTIONS {
+                let next = (position.0 + dx, position.1 + dy, position.2);
+                if !self.is_walkable(next, is_door_open) {
+                    continue;
+                }
+                if cost.get(&next).is_none_or(|&known| next_cost < known) {
+                    cost.insert(next, next_cost);
+                    came_from.insert(next, position);
+                    open.push(Reverse((next_cost + heuristic(next), next)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether nothing blocks the straight line between the two positions on the level of from.
+    /// Gaps in the floor do not block the sight.
+    pub fn has_line_of_sight(
+        &self,
+        from: GridPosition,
+        to: GridPosition,
+        is_door_open: &impl Fn(GridPosition) -> bool,
+    ) -> bool {
+        // Sample the line between the cell centers finely enough to hit every cell it crosses
+        let (dx, dy) = ((to.0 - from.0) as f32, (to.1 - from.1) as f32);
+        let steps = ((dx.abs() + dy.abs()) * 4.0).ceil() as i32;
+        (0..=steps).all(|step| {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f32 / steps as f32
+            };
+            let x = (from.0 as f32 + 0.5 + dx * t).floor() as i32;
+            let y = (from.1 as f32 + 0.5 + dy * t).floor() as i32;
+            !self.is_blocked((x, y, from.2), is_door_open)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_loader::parse_level;
+
+    fn nav_grid(level: &str) -> NavGrid {
+        NavGrid::from_level(&parse_level(level).unwrap())
+    }
+
+    #[test]
+    fn test_find_path() {
+        let doors_closed = |_| false;
+
+        // The shortest path walks around the wall
+        let grid = nav_grid("N+P N+W N\nN N N");
+        assert_eq!(
+            grid.find_path((0, 0, 1), (2, 0, 1), &doors_closed),
+            Some(vec![(0, 1, 1), (1, 1, 1), (2, 1, 1), (2, 0, 1)])
+        );
+        assert_eq!(
+            grid.find_path((0, 0, 1), (0, 0, 1), &doors_closed),
+            Some(vec![])
+        );
+
+        // Enemies can not cross gaps or walk onto walls
+        let grid = nav_grid("N+P X N");
+        assert_eq!(grid.find_path((0, 0, 1), (2, 0, 1), &doors_closed), None);
+        let grid = nav_grid("N+P N+W");
+        assert_eq!(grid.find_path((0, 0, 1), (1, 0, 1), &doors_closed), None);
+        assert!(grid.is_walkable((1, 0, 2), &doors_closed));
+    }
+
+    #[test]
+    fn test_find_path_through_doors() {
+        let grid = nav_grid("N+P N+D(#t) N N+T#t");
+        assert_eq!(grid.find_path((0, 0, 1), (2, 0, 1), &|_| false), None);
+        assert_eq!(
+            grid.find_path((0, 0, 1), (2, 0, 1), &|position| position == (1, 0, 1)),
+            Some(vec![(1, 0, 1), (2, 0, 1)])
+        );
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let doors_closed = |_| false;
+        let grid = nav_grid("N+P N N N\nN N+W N N\nN N N N");
+        assert!(grid.has_line_of_sight((0, 0, 1), (3, 0, 1), &doors_closed));
+        assert!(!grid.has_line_of_sight((0, 1, 1), (3, 1, 1), &doors_closed));
+        assert!(!grid.has_line_of_sight((0, 0, 1), (2, 2, 1), &doors_closed));
+        // Gaps do not block the sight
+        let grid = nav_grid("N+P X N");
+        assert!(grid.has_line_of_sight((0, 0, 1), (2, 0, 1), &doors_closed));
+    }
+}
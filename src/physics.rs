@@ -2,7 +2,7 @@ use bevy_ecs::system::Resource;
 use cgmath::{EuclideanSpace, InnerSpace, One};
 use rapier3d::{
     crossbeam,
-    na::{Point3, Vector3},
+    na::{Quaternion, UnitQuaternion, Vector3},
     prelude::*,
 };
 
@@ -11,6 +11,16 @@ use crate::{
     object_types::{Block, BlockType, BoxType, LinearEnemyDirection},
 };
 
+// What is needed to continue the simulation of a rigid body from a snapshot (see WorldSnapshot)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RigidBodyState {
+    pub translation: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub linear_velocity: cgmath::Vector3<f32>,
+    pub angular_velocity: cgmath::Vector3<f32>,
+    pub enabled: bool,
+}
+
 #[derive(Resource)]
 pub struct PhysicsSystem {
     integration_parameters: IntegrationParameters,
@@ -75,6 +85,11 @@ impl PhysicsSystem {
         }
     }
 
+    // Sets the acceleration along the z axis
+    pub fn set_gravity(&mut self, gravity: f32) {
+        self.gravity = vector![0.0, 0.0, gravity];
+    }
+
     pub fn step(&mut self) {
         self.physics_pipeline.step(
             &self.gravity,
@@ -114,20 +129,25 @@ impl PhysicsSystem {
             Block::FloorNormal
             | Block::Door(_)
             | Block::Wall
-            | Block::Trigger
+            | Block::Trigger(_)
+            | Block::Teleporter(_)
             | Block::Charge
-            | Block::StaticEnemy
+            | Block::StaticEnemy(_)
             | Block::Checkpoint
-            | Block::FireworkEmitter
-            | Block::Goal(_) => RigidBodyBuilder::fixed(),
+            | Block::FireworkEmitter(_)
+            | Block::Goal(..) => RigidBodyBuilder::fixed(),
             Block::Empty => unreachable!(),
+            Block::Platform(_) => RigidBodyBuilder::kinematic_position_based(),
+            Block::PatrolEnemy(..) | Block::ChasingEnemy(_) => {
+                RigidBodyBuilder::dynamic().locked_axes(LockedAxes::ROTATION_LOCKED)
+            }
             Block::Player => {
                 // make the player heaver to avoid bouncing
                 RigidBodyBuilder::dynamic().locked_axes(LockedAxes::ROTATION_LOCKED)
             }
-            Block::LinearEnemy(LinearEnemyDirection::XAxis) => RigidBodyBuilder::dynamic()
+            Block::LinearEnemy(LinearEnemyDirection::XAxis, _) => RigidBodyBuilder::dynamic()
                 .locked_axes(LockedAxes::TRANSLATION_LOCKED_Y | LockedAxes::ROTATION_LOCKED),
-            Block::LinearEnemy(LinearEnemyDirection::YAxis) => RigidBodyBuilder::dynamic()
+            Block::LinearEnemy(LinearEnemyDirection::YAxis, _) => RigidBodyBuilder::dynamic()
                 .locked_axes(LockedAxes::TRANSLATION_LOCKED_X | LockedAxes::ROTATION_LOCKED),
             Block::Box(BoxType::Free) => RigidBodyBuilder::dynamic()
                 .additional_mass(5.0)
@@ -154,17 +174,22 @@ impl PhysicsSystem {
             Block::FloorNormal
             | Block::Door(_)
             | Block::Wall
-            | Block::StaticEnemy
+            | Block::StaticEnemy(_)
+            | Block::Platform(_)
             | Block::Box(_) => Some(ColliderBuilder::cuboid(x_extent, y_extent, z_extent).build()),
-            Block::Player | Block::LinearEnemy(_) => {
+            Block::Player
+            | Block::LinearEnemy(..)
+            | Block::PatrolEnemy(..)
+            | Block::ChasingEnemy(_) => {
                 Some(ColliderBuilder::capsule_z(z_extent / 2.0, x_extent).build())
             }
             Block::Empty
             | Block::Charge
-            | Block::Trigger
-            | Block::Goal(_)
+            | Block::Trigger(_)
+            | Block::Teleporter(_)
+            | Block::Goal(..)
             | Block::Checkpoint
-            | Block::FireworkEmitter => None,
+            | Block::FireworkEmitter(_) => None,
         };
         let collider_handle = collider.map(|collider| {
             self.collider_set
@@ -201,6 +226,38 @@ impl PhysicsSystem {
         body.set_enabled(is_active);
     }
 
+    // Fixed bodies (floors, walls, ...) never move
+    pub fn is_fixed(&self, body_handle: RigidBodyHandle) -> bool {
+        self.rigid_body_set[body_handle].is_fixed()
+    }
+
+    // Moves a kinematic body to the given position during the next step
+    pub fn set_kinematic_target(
+        &mut self,
+        body_handle: RigidBodyHandle,
+        position: cgmath::Vector3<f32>,
+    ) {
+        let body = self.rigid_body_set.get_mut(body_handle).unwrap();
+        body.set_next_kinematic_translation(vector![position.x, position.y, position.z]);
+    }
+
+    // Shifts a body by the given offset without changing its velocity
+    pub fn translate_body(&mut self, body_handle: RigidBodyHandle, offset: cgmath::Vector3<f32>) {
+        let body = self.rigid_body_set.get_mut(body_handle).unwrap();
+        let translation = body.translation() + vector![offset.x, offset.y, offset.z];
+        body.set_translation(translation, true);
+    }
+
+    // Moves a body to the given position without changing its velocity
+    pub fn set_translation(
+        &mut self,
+        body_handle: RigidBodyHandle,
+        position: cgmath::Vector3<f32>,
+    ) {
+        let body = self.rigid_body_set.get_mut(body_handle).unwrap();
+        body.set_translation(vector![position.x, position.y, position.z], true);
+    }
+
     pub fn get_position(&self, body_handle: RigidBodyHandle) -> Position {
         let body = &self.rigid_body_set[body_handle];
         let pos = body.translation().clone();
@@ -216,6 +273,63 @@ impl PhysicsSystem {
         }
     }
 
+    pub fn get_rigid_body_state(&self, body_handle: RigidBodyHandle) -> RigidBodyState {
+        let body = &self.rigid_body_set[body_handle];
+        let translation = body.translation();
+        let rotation = body.rotation();
+        let linear_velocity = body.linvel();
+        let angular_velocity = body.angvel();
+
+        RigidBodyState {
+            translation: cgmath::Vector3::new(translation.x, translation.y, translation.z),
+            rotation: cgmath::Quaternion::new(rotation.w, rotation.i, rotation.j, rotation.k),
+            linear_velocity: cgmath::Vector3::new(
+                linear_velocity.x,
+                linear_velocity.y,
+                linear_velocity.z,
+            ),
+            angular_velocity: cgmath::Vector3::new(
+                angular_velocity.x,
+                angular_velocity.y,
+                angular_velocity.z,
+            ),
+            enabled: body.is_enabled(),
+        }
+    }
+
+    // Puts a body back into a state returned by get_rigid_body_state
+    pub fn restore_rigid_body_state(
+        &mut self,
+        body_handle: RigidBodyHandle,
+        state: &RigidBodyState,
+    ) {
+        let body = self.rigid_body_set.get_mut(body_handle).unwrap();
+        let translation = state.translation;
+        let rotation = state.rotation;
+        body.set_translation(vector![translation.x, translation.y, translation.z], true);
+        body.set_rotation(
+            // The rotation was taken from a body, so it is normalized already
+            UnitQuaternion::new_unchecked(Quaternion::new(
+                rotation.s,
+                rotation.v.x,
+                rotation.v.y,
+                rotation.v.z,
+            )),
+            true,
+        );
+        let linear_velocity = state.linear_velocity;
+        body.set_linvel(
+            vector![linear_velocity.x, linear_velocity.y, linear_velocity.z],
+            true,
+        );
+        let angular_velocity = state.angular_velocity;
+        body.set_angvel(
+            vector![angular_velocity.x, angular_velocity.y, angular_velocity.z],
+            true,
+        );
+        body.set_enabled(state.enabled);
+    }
+
     pub fn get_velocity_magnitude(&self, body_handle: RigidBodyHandle) -> f32 {
         let body = &self.rigid_body_set[body_handle];
         body.linvel().magnitude()
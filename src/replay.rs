@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+
+use crate::level_compressor;
+
+// Increase when the replay format changes in an incompatible way
+pub const REPLAY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayInput {
+    // The player movement from this tick on (until the next Move input)
+    Move { x: f32, y: f32 },
+    GrabPressed,
+    GrabReleased,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    // The fixed tick (counted from the start of the recording) before which the input is applied
+    pub tick: u64,
+    pub input: ReplayInput,
+}
+
+/// A recorded run of a level.
+/// Only the inputs are stored, the run is reproduced by simulating the level again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    // The seed of the game RNG during the recording
+    pub seed: u64,
+    // The number of fixed ticks the recording lasted
+    pub ticks: u64,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    /// Serializes the replay into a compressed string that can be shared in an URL
+    pub fn to_compressed_string(&self) -> String {
+        level_compressor::compress_level(&serde_json::to_string(self).unwrap())
+    }
+
+    pub fn from_compressed_string(compressed: &str) -> Result<Self, String> {
+        let json = level_compressor::decompress_level(compressed)?;
+        let replay: Replay = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!("Unsupported replay version {}", replay.version));
+        }
+        Ok(replay)
+    }
+}
+
+// Collects the inputs while a level is played
+pub struct ReplayRecorder {
+    seed: u64,
+    start_tick: u64,
+    last_movement: (f32, f32),
+    events: Vec<ReplayEvent>,
+}
+
+impl ReplayRecorder {
+    pub fn new(seed: u64, start_tick: u64) -> Self {
+        Self {
+            seed,
+            start_tick,
+            last_movement: (0.0, 0.0),
+            events: Vec::new(),
+        }
+    }
+
+    // Records the movement used in the upcoming tick if it changed
+    pub fn record_movement(&mut self, tick_count: u64, movement: (f32, f32)) {
+        if movement != self.last_movement {
+            self.last_movement = movement;
+            self.record(
+                tick_count,
+                ReplayInput::Move {
+                    x: movement.0,
+                    y: movement.1,
+                },
+            );
+        }
+    }
+
+    pub fn record(&mut self, tick_count: u64, input: ReplayInput) {
+        self.events.push(ReplayEvent {
+            tick: tick_count - self.start_tick,
+            input,
+        });
+    }
+
+    pub fn finish(self, tick_count: u64) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed: self.seed,
+            ticks: tick_count - self.start_tick,
+            events: self.events,
+        }
+    }
+}
+
+// Hands out the recorded inputs tick by tick
+pub struct ReplayPlayer {
+    replay: Replay,
+    start_tick: u64,
+    next_event: usize,
+    movement: (f32, f32),
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay, start_tick: u64) -> Self {
+        Self {
+            replay,
+            start_tick,
+            next_event: 0,
+            movement: (0.0, 0.0),
+        }
+    }
+
+    // Returns the inputs that have to be applied before the upcoming tick
+    pub fn inputs_for_tick(&mut self, tick_count: u64) -> Vec<ReplayInput> {
+        let tick = tick_count - self.start_tick;
+        let mut inputs = Vec::new();
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.tick > tick {
+                break;
+            }
+            if let ReplayInput::Move { x, y } = event.input {
+                self.movement = (x, y);
+            }
+            inputs.push(event.input.clone());
+            self.next_event += 1;
+        }
+        inputs
+    }
+
+    // The movement of the most recent Move input
+    pub fn movement(&self) -> (f32, f32) {
+        self.movement
+    }
+
+    pub fn is_finished(&self, tick_count: u64) -> bool {
+        tick_count - self.start_tick >= self.replay.ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_compressed_round_trip() {
+        let mut recorder = ReplayRecorder::new(42, 100);
+        recorder.record_movement(100, (0.0, 0.0));
+        recorder.record_movement(101, (0.5, -1.0));
+        recorder.record_movement(102, (0.5, -1.0));
+        recorder.record(105, ReplayInput::GrabPressed);
+        recorder.record_movement(110, (0.0, 0.0));
+        let replay = recorder.finish(120);
+
+        assert_eq!(replay.ticks, 20);
+        assert_eq!(
+            replay.events,
+            vec![
+                ReplayEvent {
+                    tick: 1,
+                    input: ReplayInput::Move { x: 0.5, y: -1.0 }
+                },
+                ReplayEvent {
+                    tick: 5,
+                    input: ReplayInput::GrabPressed
+                },
+                ReplayEvent {
+                    tick: 10,
+                    input: ReplayInput::Move { x: 0.0, y: 0.0 }
+                },
+            ]
+        );
+
+        let compressed = replay.to_compressed_string();
+        assert_eq!(Replay::from_compressed_string(&compressed).unwrap(), replay);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+
+use crate::world_snapshot::WorldSnapshot;
+
+/// The snapshots of the last ticks, newest last, to play the level backwards.
+/// The oldest snapshots are dropped when the snapshots take more memory than the budget allows.
+pub struct RewindBuffer {
+    snapshots: VecDeque<(WorldSnapshot, usize)>,
+    memory_budget: usize,
+    // The estimated size of all kept snapshots
+    used_memory: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(memory_budget: usize) -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+            memory_budget,
+            used_memory: 0,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: WorldSnapshot) {
+        let size = snapshot.estimated_size();
+        self.snapshots.push_back((snapshot, size));
+        self.used_memory += size;
+        while self.used_memory > self.memory_budget {
+            let Some((_, size)) = self.snapshots.pop_front() else {
+                break;
+            };
+            self.used_memory -= size;
+        }
+    }
+
+    /// Takes the newest snapshot, None if the buffer has been played back completely
+    pub fn pop(&mut self) -> Option<WorldSnapshot> {
+        let (snapshot, size) = self.snapshots.pop_back()?;
+        self.used_memory -= size;
+        Some(snapshot)
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.used_memory = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn used_memory(&self) -> usize {
+        self.used_memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn snapshot(level_key: &str) -> WorldSnapshot {
+        WorldSnapshot {
+            level_key: level_key.to_string(),
+            entities: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_rewind_buffer_keeps_memory_budget() {
+        let size = snapshot("a").estimated_size();
+        let mut rewind_buffer = RewindBuffer::new(3 * size);
+        for level_key in ["a", "b", "c", "d"] {
+            rewind_buffer.push(snapshot(level_key));
+        }
+
+        // The oldest snapshot was dropped
+        assert_eq!(rewind_buffer.len(), 3);
+        assert_eq!(rewind_buffer.used_memory(), 3 * size);
+        assert_eq!(rewind_buffer.pop(), Some(snapshot("d")));
+        assert_eq!(rewind_buffer.pop(), Some(snapshot("c")));
+        assert_eq!(rewind_buffer.pop(), Some(snapshot("b")));
+        assert_eq!(rewind_buffer.pop(), None);
+        assert_eq!(rewind_buffer.used_memory(), 0);
+    }
+}
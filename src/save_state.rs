@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::level_pack::LevelPackProgress;
+
+// Increase when the save state format changes in an incompatible way
+pub const SAVE_STATE_VERSION: u32 = 1;
+
+// The choices of the player that hold for all levels
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub eye_distance: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelSave {
+    pub completed: bool,
+    // The fastest time in seconds the level was completed in without resuming at a checkpoint
+    pub best_time: Option<f32>,
+    // The checkpoint the player starts at when the level is loaded again, cleared when the level is completed
+    pub checkpoint: Option<String>,
+}
+
+/// Everything that has to survive a reload of the game.
+/// The host page keeps it in the localStorage, the native build in a file in the config dir.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveState {
+    pub version: u32,
+    pub settings: Settings,
+    // Keyed by the content_key() of the level text
+    pub levels: BTreeMap<String, LevelSave>,
+    // Keyed by the content_key() of the level pack
+    pub level_packs: BTreeMap<String, LevelPackProgress>,
+}
+
+impl Default for SaveState {
+    fn default() -> Self {
+        Self {
+            version: SAVE_STATE_VERSION,
+            settings: Settings::default(),
+            levels: BTreeMap::new(),
+            level_packs: BTreeMap::new(),
+        }
+    }
+}
+
+impl SaveState {
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json_string(json: &str) -> Result<Self, String> {
+        let save_state: SaveState = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if save_state.version != SAVE_STATE_VERSION {
+            return Err(format!(
+                "Unsupported save state version {}",
+                save_state.version
+            ));
+        }
+        Ok(save_state)
+    }
+
+    /// Marks the level as completed and keeps the time if it is the best one.
+    /// The time is None if the run does not count, e.g. because it was resumed at a checkpoint.
+    pub fn complete_level(&mut self, level_key: &str, time: Option<f32>) {
+        let level = self.levels.entry(level_key.to_string()).or_default();
+        level.completed = true;
+        level.checkpoint = None;
+        if let Some(time) = time {
+            if level.best_time.is_none_or(|best_time| time < best_time) {
+                level.best_time = Some(time);
+            }
+        }
+    }
+
+    pub fn set_checkpoint(&mut self, level_key: &str, checkpoint: String) {
+        self.levels
+            .entry(level_key.to_string())
+            .or_default()
+            .checkpoint = Some(checkpoint);
+    }
+
+    pub fn checkpoint(&self, level_key: &str) -> Option<&str> {
+        self.levels.get(level_key)?.checkpoint.as_deref()
+    }
+
+    // The file the native build keeps the save state in
+    #[cfg(not(target_arch = "wasm32"))]
+    fn file_path() -> Option<std::path::PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("stereo-glitch").join("save_state.json"))
+    }
+
+    /// Reads the save state of the native build, None if there is none yet or it can not be read
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_from_file() -> Option<Self> {
+        let json = std::fs::read_to_string(Self::file_path()?).ok()?;
+        match Self::from_json_string(&json) {
+            Ok(save_state) => Some(save_state),
+            Err(e) => {
+                log::warn!("Failed to read the save state: {}", e);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_to_file(&self) -> Result<(), String> {
+        let path = Self::file_path().ok_or("No config dir found")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, self.to_json_string()).map_err(|e| e.to_string())
+    }
+}
+
+/// Identifies a level or level pack across sessions by its content (64 bit FNV-1a hash).
+/// Unlike the std hashers the hash is guaranteed to stay the same between builds.
+pub fn content_key(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_state_roundtrip() {
+        let mut save_state = SaveState::default();
+        save_state.settings.eye_distance = Some(0.2);
+        save_state.set_checkpoint("a", "cp".to_string());
+        save_state.complete_level("b", Some(12.5));
+        save_state.complete_level("b", Some(14.0));
+        save_state.complete_level("b", None);
+
+        assert_eq!(save_state.checkpoint("a"), Some("cp"));
+        assert_eq!(save_state.levels["b"].best_time, Some(12.5));
+        assert_eq!(
+            SaveState::from_json_string(&save_state.to_json_string()),
+            Ok(save_state.clone())
+        );
+
+        // Completing a level forgets its checkpoint
+        save_state.complete_level("a", None);
+        assert_eq!(save_state.checkpoint("a"), None);
+        assert_eq!(save_state.levels["a"].best_time, None);
+
+        save_state.version = SAVE_STATE_VERSION + 1;
+        assert_eq!(
+            SaveState::from_json_string(&save_state.to_json_string()),
+            Err(format!(
+                "Unsupported save state version {}",
+                SAVE_STATE_VERSION + 1
+            ))
+        );
+    }
+
+    #[test]
+    fn test_content_key_is_stable() {
+        assert_eq!(content_key(""), "cbf29ce484222325");
+        assert_eq!(content_key("N+P"), content_key("N+P"));
+        assert_ne!(content_key("N+P"), content_key("P+N"));
+    }
+}
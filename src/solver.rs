@@ -0,0 +1,646 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_objects::constants::MAX_PLAYER_CHARGE;
+use crate::game_objects::game_rules::GameRules;
+use crate::level_loader::ParsedLevel;
+use crate::object_types::{Block, BoxType, DoorCondition, Id, TriggerMode};
+
+// The search is aborted after this many states so that checking a level stays fast enough for the editor
+const MAX_SEARCH_STATES: usize = 20_000;
+
+// Movables within this many cells of the player can be grabbed
+const GRAB_RANGE: i32 = 2;
+
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Solvability {
+    // The goal can be reached with the given number of moves from cell to cell
+    Solvable { moves: usize },
+    Unsolvable,
+    // The level has no goal that could be reached
+    NoGoal,
+    // The search was aborted before a result was found
+    Undecided,
+}
+
+// A position in the level of the form (x, y, level)
+// The level is the index in the block stack, a player on level 1 stands on a block of level 0
+type GridPosition = (i32, i32, i32);
+
+// The charge rules of the level (see GameRules), charge is counted in glitch cells the player can cross
+struct ChargeModel {
+    // Without depletion the player crosses glitch areas for free
+    depletes: bool,
+    per_pickup: u32,
+    max: u32,
+    // The moves until a used charge pickup can be used again
+    cooldown_moves: u16,
+}
+
+impl ChargeModel {
+    fn new(game_rules: &GameRules) -> Self {
+        // The player crosses player_speed cells in the second it takes to deplete charge_depletion charge
+        let glitch_cells_per_charge = game_rules.player_speed / game_rules.charge_depletion;
+        Self {
+            depletes: game_rules.charge_depletion > 0.0,
+            per_pickup: (game_rules.recharge_amount * glitch_cells_per_charge) as u32,
+            max: (MAX_PLAYER_CHARGE * glitch_cells_per_charge) as u32,
+            cooldown_moves: (game_rules.recharge_cooldown * game_rules.player_speed) as u16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Occupant {
+    Free,
+    Solid,
+    // A door with the given index in LevelModel::doors
+    Door(usize),
+}
+
+struct Column {
+    occupants: Vec<Occupant>,
+    is_glitch_area: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct SearchState {
+    player: GridPosition,
+    // Sorted by position so that equal states compare equal
+    boxes: Vec<(GridPosition, BoxType)>,
+    // Which triggers are active, by trigger index
+    active_triggers: Vec<bool>,
+    // The moves until a charge pickup can be used again, by charge index
+    charge_cooldowns: Vec<u16>,
+}
+
+// An abstract model of the level on the grid.
+// Physics are approximated: the player can not climb, only walk on the same level or fall down.
+// Boxes can be pushed or dragged along their axis while grabbed, enemies are ignored except for static ones which are solid.
+struct LevelModel {
+    width: usize,
+    // Row by row, cells missing in short rows are empty columns
+    columns: Vec<Column>,
+    triggers: HashMap<GridPosition, usize>,
+    trigger_indices: HashMap<Id, usize>,
+    trigger_modes: HashMap<usize, TriggerMode>,
+    doors: Vec<DoorCondition>,
+    // The index of every charge pickup
+    charges: HashMap<GridPosition, usize>,
+    charge_model: ChargeModel,
+    goals: HashSet<GridPosition>,
+    player: Option<GridPosition>,
+    boxes: Vec<(GridPosition, BoxType)>,
+    // Moving platforms and teleporters are not modeled, levels with them can not be decided
+    has_unmodeled_blocks: bool,
+}
+
+impl LevelModel {
+    fn new(level: &ParsedLevel) -> Self {
+        let mut trigger_indices: HashMap<Id, usize> = HashMap::new();
+        let mut trigger_index = |id: &Id| {
+            let next_index = trigger_indices.len();
+            *trigger_indices.entry(id.clone()).or_insert(next_index)
+        };
+
+        let (width, height, _depth) = level.dimensions();
+        let mut model = Self {
+            width,
+            columns: (0..width * height)
+                .map(|_| Column {
+                    occupants: Vec::new(),
+                    is_glitch_area: false,
+                })
+                .collect(),
+            triggers: HashMap::new(),
+            trigger_indices: HashMap::new(),
+            trigger_modes: HashMap::new(),
+            doors: Vec::new(),
+            charges: HashMap::new(),
+            charge_model: ChargeModel::new(&GameRules::default().with_metadata(level.metadata())),
+            goals: HashSet::new(),
+            player: None,
+            boxes: Vec::new(),
+            has_unmodeled_blocks: false,
+        };
+
+        for ((x, y), cell) in level.iter_cells() {
+            let mut occupants = Vec::new();
+            let mut z: f32 = 0.0;
+            for (block, id) in cell.block_stack_iter() {
+                // Triggers and firework emitters are flat and lie on top of the block below
+                let level = z.round() as i32;
+                let position = (x, y, level);
+                let occupant = match block {
+                    Block::FloorNormal | Block::Wall | Block::StaticEnemy(_) => Occupant::Solid,
+                    Block::Door(condition) => {
+                        for id in condition.trigger_ids() {
+                            trigger_index(id);
+                        }
+                        model.doors.push(condition.clone());
+                        Occupant::Door(model.doors.len() - 1)
+                    }
+                    Block::Trigger(mode) => {
+                        if let Some(id) = id {
+                            let trigger = trigger_index(id);
+                            model.triggers.insert(position, trigger);
+                            model.trigger_modes.insert(trigger, *mode);
+                        }
+                        Occupant::Free
+                    }
+                    Block::Box(box_type) => {
+                        model.boxes.push((position, box_type.clone()));
+                        Occupant::Free
+                    }
+                    Block::Charge => {
+                        let charge = model.charges.len();
+                        model.charges.insert(position, charge);
+                        Occupant::Free
+                    }
+                    Block::Goal(..) => {
+                        model.goals.insert(position);
+                        Occupant::Free
+                    }
+                    Block::Player => {
+                        model.player = Some(position);
+                        Occupant::Free
+                    }
+                    Block::Empty
+                    | Block::LinearEnemy(..)
+                    | Block::PatrolEnemy(..)
+                    | Block::ChasingEnemy(_)
+                    | Block::Checkpoint
+                    | Block::FireworkEmitter(_) => Occupant::Free,
+                    Block::Platform(_) => {
+                        model.has_unmodeled_blocks = true;
+                        Occupant::Solid
+                    }
+                    Block::Teleporter(_) => {
+                        model.has_unmodeled_blocks = true;
+                        Occupant::Free
+                    }
+                };
+                if block.block_height() >= 1.0 {
+                    occupants.resize(level as usize + 1, Occupant::Free);
+                    occupants[level as usize] = occupant;
+                }
+                z += block.block_height();
+            }
+            model.columns[y as usize * width + x as usize] = Column {
+                occupants,
+                is_glitch_area: cell.is_glitch_area(),
+            };
+        }
+        model.trigger_indices = trigger_indices;
+        model.boxes.sort_by_key(|(position, _)| *position);
+
+        model
+    }
+
+    fn column(&self, x: i32, y: i32) -> Option<&Column> {
+        if x < 0 || y < 0 || x as usize >= self.width {
+            return None;
+        }
+        self.columns.get(y as usize * self.width + x as usize)
+    }
+
+    fn occupant(&self, (x, y, level): GridPosition) -> Occupant {
+        if level < 0 {
+            return Occupant::Free;
+        }
+        self.column(x, y)
+            .and_then(|column| column.occupants.get(level as usize))
+            .copied()
+            .unwrap_or(Occupant::Free)
+    }
+
+    fn is_glitch_area(&self, (x, y, _): GridPosition) -> bool {
+        self.column(x, y)
+            .map(|column| column.is_glitch_area)
+            .unwrap_or(false)
+    }
+
+    // Returns which triggers are pressed by the player (if given) or the boxes
+    fn pressed_triggers(
+        &self,
+        player: Option<GridPosition>,
+        boxes: &[(GridPosition, BoxType)],
+    ) -> Vec<bool> {
+        let mut pressed = vec![false; self.trigger_indices.len()];
+        for position in player
+            .iter()
+            .chain(boxes.iter().map(|(position, _)| position))
+        {
+            if let Some(&trigger) = self.triggers.get(position) {
+                pressed[trigger] = true;
+            }
+        }
+        pressed
+    }
+
+    // Returns which triggers are active after the pressed triggers changed
+    // Timed triggers are assumed to stay active long enough, like latching ones
+    fn active_triggers(
+        &self,
+        pressed: &[bool],
+        pressed_before: &[bool],
+        active_before: &[bool],
+    ) -> Vec<bool> {
+        (0..pressed.len())
+            .map(|trigger| match self.trigger_modes.get(&trigger) {
+                Some(TriggerMode::Latching | TriggerMode::Timed(_)) => {
+                    active_before[trigger] || pressed[trigger]
+                }
+                Some(TriggerMode::Toggle) => {
+                    active_before[trigger] != (pressed[trigger] && !pressed_before[trigger])
+                }
+                Some(TriggerMode::Momentary) | None => pressed[trigger],
+            })
+            .collect()
+    }
+
+    fn is_door_open(&self, door: usize, active_triggers: &[bool]) -> bool {
+        self.doors[door].is_satisfied(&|id| active_triggers[self.trigger_indices[id]])
+    }
+
+    fn is_blocked(
+        &self,
+        position: GridPosition,
+        active_triggers: &[bool],
+        boxes: &[(GridPosition, BoxType)],
+    ) -> bool {
+        match self.occupant(position) {
+            Occupant::Solid => true,
+            Occupant::Door(door) => !self.is_door_open(door, active_triggers),
+            Occupant::Free => boxes
+                .iter()
+                .any(|(box_position, _)| *box_position == position),
+        }
+    }
+
+    // Returns the level on which something entering the given position comes to rest
+    // or None if the position is blocked or it would fall out of the level
+    fn landing_level(
+        &self,
+        (x, y, level): GridPosition,
+        active_triggers: &[bool],
+        boxes: &[(GridPosition, BoxType)],
+    ) -> Option<i32> {
+        if self.is_blocked((x, y, level), active_triggers, boxes) {
+            return None;
+        }
+        let mut level = level;
+        while level > 0 {
+            if self.is_blocked((x, y, level - 1), active_triggers, boxes) {
+                return Some(level);
+            }
+            level -= 1;
+        }
+        None
+    }
+
+    // Moves the box with the given index one cell into the direction if possible
+    // Boxes are not allowed to fall down
+    fn try_move_box(
+        &self,
+        boxes: &mut [(GridPosition, BoxType)],
+        index: usize,
+        (dx, dy): (i32, i32),
+        active_triggers: &[bool],
+    ) -> bool {
+        let ((x, y, level), box_type) = &boxes[index];
+        let can_move_along_axis = match box_type {
+            BoxType::XAxis => dy == 0,
+            BoxType::YAxis => dx == 0,
+            BoxType::Free | BoxType::RotationFixed => true,
+        };
+        let target = (x + dx, y + dy, *level);
+        if !can_move_along_axis
+            || self.landing_level(target, active_triggers, boxes) != Some(*level)
+        {
+            return false;
+        }
+        boxes[index].0 = target;
+        true
+    }
+
+    // Returns the state and charge after moving the player one cell into the direction
+    // or None if the move is not possible
+    fn step(
+        &self,
+        state: &SearchState,
+        charge: u32,
+        direction: (i32, i32),
+        drag: bool,
+    ) -> Option<(SearchState, u32)> {
+        let (x, y, level) = state.player;
+        let target = (x + direction.0, y + direction.1, level);
+        let pressed_before = self.pressed_triggers(Some(state.player), &state.boxes);
+        let active_before = &state.active_triggers;
+        let mut boxes = state.boxes.clone();
+
+        // All boxes in range follow the player as long as they are not blocked
+        if drag {
+            let mut in_range = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, ((box_x, box_y, box_level), _))| {
+                    (box_x - x).abs() <= GRAB_RANGE
+                        && (box_y - y).abs() <= GRAB_RANGE
+                        && (box_level - level).abs() <= GRAB_RANGE
+                })
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            let grabbed_count = in_range.len();
+            // Boxes blocked by other grabbed boxes might be able to move once those have moved
+            loop {
+                let count_before = in_range.len();
+                in_range.retain(|&index| {
+                    !self.try_move_box(&mut boxes, index, direction, active_before)
+                });
+                if in_range.len() == count_before {
+                    break;
+                }
+            }
+            // Without any moved box this is the same as not dragging
+            if in_range.len() == grabbed_count {
+                return None;
+            }
+        }
+
+        // Push the box the player walks into
+        if let Some(index) = boxes.iter().position(|(position, _)| *position == target) {
+            if !self.try_move_box(&mut boxes, index, direction, active_before) {
+                return None;
+            }
+        }
+        boxes.sort_by_key(|(position, _)| *position);
+
+        // Doors might close when the player leaves a trigger, so the landing has to be checked with the new trigger state
+        let pressed_by_boxes = self.pressed_triggers(None, &boxes);
+        let active_by_boxes =
+            self.active_triggers(&pressed_by_boxes, &pressed_before, active_before);
+        let landing_level = self.landing_level(target, &active_by_boxes, &boxes)?;
+        let player = (target.0, target.1, landing_level);
+        let pressed_after = self.pressed_triggers(Some(player), &boxes);
+        let active_after = self.active_triggers(&pressed_after, &pressed_before, active_before);
+        if self.landing_level(target, &active_after, &boxes) != Some(landing_level) {
+            return None;
+        }
+        // A closing door would crush a box
+        let box_crushed = boxes.iter().any(|(position, _)| {
+            matches!(self.occupant(*position), Occupant::Door(door) if !self.is_door_open(door, &active_after))
+        });
+        if box_crushed {
+            return None;
+        }
+
+        let mut charge_cooldowns = state
+            .charge_cooldowns
+            .iter()
+            .map(|cooldown| cooldown.saturating_sub(1))
+            .collect::<Vec<_>>();
+        let charge = self.charge_after_entering(player, charge, &mut charge_cooldowns)?;
+        Some((
+            SearchState {
+                player,
+                boxes,
+                active_triggers: active_after,
+                charge_cooldowns,
+            },
+            charge,
+        ))
+    }
+
+    // Returns the charge after the player entered the position or None if the player runs out of charge
+    // A used charge pickup starts its cooldown, walking over it again only helps once it is over
+    fn charge_after_entering(
+        &self,
+        position: GridPosition,
+        charge: u32,
+        charge_cooldowns: &mut [u16],
+    ) -> Option<u32> {
+        let mut charge = charge;
+        if let Some(&pickup) = self.charges.get(&position) {
+            if charge_cooldowns[pickup] == 0 {
+                charge = charge
+                    .saturating_add(self.charge_model.per_pickup)
+                    .min(self.charge_model.max);
+                charge_cooldowns[pickup] = self.charge_model.cooldown_moves;
+            }
+        }
+        if self.charge_model.depletes && self.is_glitch_area(position) {
+            charge = charge.checked_sub(1)?;
+        }
+        Some(charge)
+    }
+
+    fn solve(&self) -> Solvability {
+        if self.goals.is_empty() {
+            return Solvability::NoGoal;
+        }
+        if self.has_unmodeled_blocks {
+            return Solvability::Undecided;
+        }
+        let Some(player) = self.player else {
+            return Solvability::Unsolvable;
+        };
+        let mut charge_cooldowns = vec![0; self.charges.len()];
+        let Some(charge) = self.charge_after_entering(player, 0, &mut charge_cooldowns) else {
+            return Solvability::Unsolvable;
+        };
+        let pressed = self.pressed_triggers(Some(player), &self.boxes);
+        let released = vec![false; pressed.len()];
+        let start = SearchState {
+            player,
+            boxes: self.boxes.clone(),
+            active_triggers: self.active_triggers(&pressed, &released, &released),
+            charge_cooldowns,
+        };
+        if self.goals.contains(&start.player) {
+            return Solvability::Solvable { moves: 0 };
+        }
+
+        // Breadth first search, a state is only visited again if the player has more charge than before
+        let mut max_charge_visited = HashMap::new();
+        max_charge_visited.insert(start.clone(), charge);
+        let mut queue = VecDeque::new();
+        queue.push_back((start, charge, 0));
+
+        while let Some((state, charge, moves)) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                for drag in [false, true] {
+                    let Some((next_state, next_charge)) =
+                        self.step(&state, charge, direction, drag)
+                    else {
+                        continue;
+                    };
+                    if self.goals.contains(&next_state.player) {
+                        return Solvability::Solvable { moves: moves + 1 };
+                    }
+                    let visited_charge = max_charge_visited.get(&next_state);
+                    if visited_charge.is_some_and(|&visited_charge| visited_charge >= next_charge) {
+                        continue;
+                    }
+                    if max_charge_visited.len() >= MAX_SEARCH_STATES {
+                        return Solvability::Undecided;
+                    }
+                    max_charge_visited.insert(next_state.clone(), next_charge);
+                    queue.push_back((next_state, next_charge, moves + 1));
+                }
+            }
+        }
+
+        Solvability::Unsolvable
+    }
+}
+
+/// Checks whether a goal of the level can be reached from the player.
+/// This is a search on an abstract model of the level, it does not simulate the physics.
+pub fn check_solvability(level: &ParsedLevel) -> Solvability {
+    LevelModel::new(level).solve()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_loader::parse_level;
+
+    fn solvability(level: &str) -> Solvability {
+        check_solvability(&parse_level(level).unwrap())
+    }
+
+    #[test]
+    fn test_walk_to_goal() {
+        assert_eq!(
+            solvability("N+P N N+G(END)"),
+            Solvability::Solvable { moves: 2 }
+        );
+        assert_eq!(solvability("N+P N"), Solvability::NoGoal);
+        // The player can not cross a gap
+        assert_eq!(solvability("N+P X N+G(END)"), Solvability::Unsolvable);
+        // The player can not climb walls
+        assert_eq!(solvability("N+P N+W N+G(END)"), Solvability::Unsolvable);
+    }
+
+    #[test]
+    fn test_door_needs_box_on_trigger() {
+        // Standing on the trigger does not help as the door closes when the player leaves it
+        assert_eq!(
+            solvability("N+P N+T#t N+D(#t) N+G(END)"),
+            Solvability::Unsolvable
+        );
+        // The box has to be pushed onto the trigger and then the player walks around it
+        assert_eq!(
+            solvability("N+P N+BX N N+T#t N+W N+W\nN N N N N+D(#t) N+G(END)"),
+            Solvability::Solvable { moves: 6 }
+        );
+        // An axis locked box can only be pushed along its axis
+        let level = "N+P N N+W\nN+BOX N N+W\nN+T#t N N+W\nN+W N+D(#t) N+G(END)";
+        assert_eq!(
+            solvability(&level.replace("BOX", "BY")),
+            Solvability::Solvable { moves: 5 }
+        );
+        assert_eq!(
+            solvability(&level.replace("BOX", "BX")),
+            Solvability::Unsolvable
+        );
+    }
+
+    #[test]
+    fn test_door_conditions() {
+        // The door opens as soon as the player leaves the trigger
+        assert_eq!(
+            solvability("N+P N+T#t N+D(!#t) N+G(END)"),
+            Solvability::Solvable { moves: 3 }
+        );
+        // The box can only be pushed onto one of the triggers
+        let level = "N+P N+BX N+T#t N N+D(CONDITION) N+G(END)\nN+T#u N N N N+W N+W";
+        assert_eq!(
+            solvability(&level.replace("CONDITION", "#t|#u")),
+            Solvability::Solvable { moves: 7 }
+        );
+        assert_eq!(
+            solvability(&level.replace("CONDITION", "#t&#u")),
+            Solvability::Unsolvable
+        );
+    }
+
+    #[test]
+    fn test_stateful_triggers_keep_doors_open() {
+        for (trigger, solvability_expected) in [
+            ("T", Solvability::Unsolvable),
+            ("TL", Solvability::Solvable { moves: 3 }),
+            ("TG", Solvability::Solvable { moves: 3 }),
+            ("T(5)", Solvability::Solvable { moves: 3 }),
+        ] {
+            let level = format!("N+P N+{}#t N+D(#t) N+G(END)", trigger);
+            assert_eq!(solvability(&level), solvability_expected, "{}", level);
+        }
+    }
+
+    #[test]
+    fn test_glitch_area_needs_charge() {
+        assert_eq!(solvability("N+P _N _N N+G(END)"), Solvability::Unsolvable);
+        assert_eq!(
+            solvability("N+P N+C _N _N N+G(END)"),
+            Solvability::Solvable { moves: 4 }
+        );
+    }
+
+    #[test]
+    fn test_charge_pickup_cooldown() {
+        let glitch_cells = |n: usize| vec!["_N"; n].join(" ");
+        // The player falls onto the pickup and down again, so it can only be used once
+        // Its charge lasts for 140 glitch cells
+        let one_pickup = |n: usize| format!("N+N+N+P N+N+C N {} N+G(END)", glitch_cells(n));
+        assert_eq!(
+            solvability(&one_pickup(140)),
+            Solvability::Solvable { moves: 143 }
+        );
+        assert_eq!(solvability(&one_pickup(141)), Solvability::Unsolvable);
+
+        // Walking back and forth over the pickup only helps once its cooldown is over
+        assert_eq!(
+            solvability(&format!("N+P N+C N {} N+G(END)", glitch_cells(141))),
+            Solvability::Solvable {
+                moves: 1 + ChargeModel::new(&GameRules::default()).cooldown_moves as usize + 143
+            }
+        );
+    }
+
+    #[test]
+    fn test_charge_rules_of_the_level() {
+        let glitch_cells = |n: usize| vec!["_N"; n].join(" ");
+        let one_pickup = |rules: &str, n: usize| {
+            format!("{}\n\nN+N+N+P N+N+C N {} N+G(END)", rules, glitch_cells(n))
+        };
+        // Twice the depletion halves the glitch cells a pickup lasts for
+        assert_eq!(
+            solvability(&one_pickup("charge_depletion: 2", 70)),
+            Solvability::Solvable { moves: 73 }
+        );
+        assert_eq!(
+            solvability(&one_pickup("charge_depletion: 2", 71)),
+            Solvability::Unsolvable
+        );
+        // A pickup fills the charge up to the maximum of 100 at most
+        let full_charge = "charge_depletion: 4\nrecharge_amount: 200";
+        assert_eq!(
+            solvability(&one_pickup(full_charge, 175)),
+            Solvability::Solvable { moves: 178 }
+        );
+        assert_eq!(
+            solvability(&one_pickup(full_charge, 176)),
+            Solvability::Unsolvable
+        );
+        // Without depletion no charge is needed at all
+        assert_eq!(
+            solvability("charge_depletion: 0\n\nN+P _N _N N+G(END)"),
+            Solvability::Solvable { moves: 3 }
+        );
+    }
+}
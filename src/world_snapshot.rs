@@ -0,0 +1,101 @@
+use std::{collections::HashMap, mem::size_of};
+
+use cgmath::Vector3;
+
+use crate::{
+    game_objects::{position::Position, trigger::Trigger},
+    object_types::Id,
+    pathfinding::GridPosition,
+    physics::RigidBodyState,
+};
+
+/// Identifies an entity across resets of the level.
+/// The player has its own key as it is spawned at the checkpoint block after a reset.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SnapshotKey {
+    Player,
+    Block(Id),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorSnapshot {
+    pub triggered: bool,
+    // The entities inside the sensor
+    pub triggered_by: Vec<SnapshotKey>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlatformSnapshot {
+    pub progress: f32,
+    pub moving_to_end: bool,
+}
+
+// The state the movement of an enemy depends on
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnemySnapshot {
+    Linear {
+        direction: Vector3<f32>,
+        stuck_counter: u32,
+    },
+    Patrol {
+        target: usize,
+        forward: bool,
+    },
+    Chasing {
+        chasing: bool,
+        path: Vec<GridPosition>,
+        repath_ticks: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntitySnapshot {
+    pub position: Position,
+    pub body: RigidBodyState,
+    // The charge of the player
+    pub charge: Option<f32>,
+    pub sensor: Option<SensorSnapshot>,
+    // The cooldown of a charge block
+    pub charge_cooldown: Option<f32>,
+    pub trigger: Option<Trigger>,
+    pub platform: Option<PlatformSnapshot>,
+    pub enemy: Option<EnemySnapshot>,
+    // The remaining ticks of a teleport cooldown, None if the entity can be teleported
+    pub teleport_cooldown: Option<u32>,
+    // The objects the player is pulling
+    pub pulled_objects: Vec<SnapshotKey>,
+}
+
+/// The state of all entities spawned for the blocks of a level.
+/// GameWorld::restore_snapshot brings the level back to it, also after the level was reset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldSnapshot {
+    // The level the snapshot was taken in, see save_state::content_key
+    pub level_key: String,
+    pub entities: HashMap<SnapshotKey, EntitySnapshot>,
+}
+
+impl WorldSnapshot {
+    /// The approximate memory the snapshot takes, the IDs of the blocks are not counted
+    pub fn estimated_size(&self) -> usize {
+        let entities_size: usize = self
+            .entities
+            .values()
+            .map(|entity| {
+                let chase_path_size = match &entity.enemy {
+                    Some(EnemySnapshot::Chasing { path, .. }) => {
+                        path.len() * size_of::<GridPosition>()
+                    }
+                    _ => 0,
+                };
+                size_of::<(SnapshotKey, EntitySnapshot)>()
+                    + entity.sensor.as_ref().map_or(0, |sensor| {
+                        sensor.triggered_by.len() * size_of::<SnapshotKey>()
+                    })
+                    + entity.pulled_objects.len() * size_of::<SnapshotKey>()
+                    + chase_path_size
+            })
+            .sum();
+        size_of::<Self>() + self.level_key.len() + entities_size
+    }
+}
@@ -1,15 +1,29 @@
 import nipplejs from 'nipplejs';
-import init, { load_level, set_eye_distance, set_size, joystick_input, action_button_pressed, action_button_released, compress_level_to_url, decompress_level_from_url, check_level } from "../pkg/stereo_glitch.js";
+import init, { load_level, set_eye_distance, set_size, joystick_input, action_button_pressed, action_button_released, compress_level_to_url, decompress_level_from_url, check_level, level_metadata, set_game_rules, get_game_status, take_game_events, load_level_pack, load_pack_level, compress_level_pack, export_save_state, import_save_state, quick_save, quick_load, rewind_button_pressed, rewind_button_released, undo_box_push } from "../pkg/stereo_glitch.js";
 import { basicSetup, EditorView } from "codemirror"
 
 // export the functions 
-export { load_level, set_eye_distance, compress_level_to_url, decompress_level_from_url };
+export { load_level, set_eye_distance, compress_level_to_url, decompress_level_from_url, level_metadata, set_game_rules, get_game_status, take_game_events, load_level_pack, load_pack_level, compress_level_pack, export_save_state, import_save_state, quick_save, quick_load, rewind_button_pressed, rewind_button_released, undo_box_push };
 
 // make the function available to the window
 window.load_level = load_level;
 window.set_eye_distance = set_eye_distance;
 window.compress_level_to_url = compress_level_to_url;
 window.decompress_level_from_url = decompress_level_from_url;
+window.level_metadata = level_metadata;
+window.set_game_rules = set_game_rules;
+window.get_game_status = get_game_status;
+window.take_game_events = take_game_events;
+window.load_level_pack = load_level_pack;
+window.load_pack_level = load_pack_level;
+window.compress_level_pack = compress_level_pack;
+window.export_save_state = export_save_state;
+window.import_save_state = import_save_state;
+window.quick_save = quick_save;
+window.quick_load = quick_load;
+window.rewind_button_pressed = rewind_button_pressed;
+window.rewind_button_released = rewind_button_released;
+window.undo_box_push = undo_box_push;
 
 // https://stackoverflow.com/questions/11381673/detecting-a-mobile-browser
 window.mobileCheck = function () {
@@ -153,18 +167,29 @@ let parserWithMetadata = parser.configure({
     props: [
         styleTags({
             Concat: t.operator,
+            "Not And Or": t.logicOperator,
             Glitch: t.invalid,
             NormalFloor: t.typeName,
             Wall: t.typeName,
             Enemy: t.typeName,
+            PatrolEnemy: t.typeName,
+            PatrolSegment: t.number,
             Goal: t.typeName,
             Charge: t.typeName,
+            Checkpoint: t.typeName,
+            FireworkEmitter: t.typeName,
             Trigger: t.typeName,
             Door: t.typeName,
+            Platform: t.typeName,
+            Teleporter: t.typeName,
             Player: t.typeName,
             Box: t.typeName,
             Id: t.controlKeyword,
-            "( )": t.paren
+            PropertyKey: t.propertyName,
+            PropertyValue: t.number,
+            Metadata: t.comment,
+            "( )": t.paren,
+            "{ }": t.brace
         })
     ]
 });
@@ -188,40 +213,50 @@ const levelLinter = linter(view => {
         let loadButton = document.getElementById("load-button")!;
         loadButton.classList.remove("is-active");
 
-        let parsedFailedRest = json.contents?.ParseFailed?.rest;
-        let validationError = json.contents?.ValidationError?.message;
+        let syntaxErrors = json.contents?.ParseFailed?.errors ?? [];
+        let validationErrors = json.contents?.ValidationError?.errors ?? [];
 
-        if (parsedFailedRest !== undefined) {
-
-            // Find the start position in the edit buffer
-            let startPos = level.indexOf(parsedFailedRest);
-            // The end position is for now just one character after the start position
-            let endPos = startPos + 1;
+        // The spans are byte offsets into the UTF-8 encoded level, CodeMirror counts UTF-16 code units
+        let levelBytes = new TextEncoder().encode(level);
+        let toDocOffset = (byteOffset: number) => new TextDecoder().decode(levelBytes.subarray(0, byteOffset)).length;
 
+        for (let error of syntaxErrors) {
+            let found = error.found === "" ? "end of cell" : `"${error.found}"`;
+            let from = toDocOffset(error.span.start);
             diagnostics.push({
-                from: startPos,
-                to: endPos,
+                from,
+                // Mark at least one character so that errors at the end of a cell are visible
+                to: Math.min(Math.max(toDocOffset(error.span.end), from + 1), level.length),
                 severity: "error",
-                message: "Failed to parse level",
+                message: `Unexpected ${found} at ${error.span.line}:${error.span.column}, expected one of ${error.expected.join(" ")}`,
                 actions: [],
             })
         }
 
-        if (validationError !== undefined) {
-            let startPos = 0;
-            let endPos = level.length;
-
+        for (let error of validationErrors) {
+            // Errors without a span concern the whole level
             diagnostics.push({
-                from: startPos,
-                to: endPos,
+                from: error.span ? toDocOffset(error.span.start) : 0,
+                to: error.span ? toDocOffset(error.span.end) : level.length,
                 severity: "warning",
-                message: validationError,
+                message: error.message,
                 actions: [],
             })
         }
     } else if (json.result === "ok") {
         let loadButton = document.getElementById("load-button")!;
         loadButton.classList.add("is-active");
+
+        // The level can still be loaded, but it can't be finished
+        if (json.solvability === "Unsolvable") {
+            diagnostics.push({
+                from: 0,
+                to: level.length,
+                severity: "warning",
+                message: "Level is unsolvable: the goal can't be reached from the player",
+                actions: [],
+            })
+        }
     }
     return diagnostics
 });